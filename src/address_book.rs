@@ -1,10 +1,10 @@
 use ethers::prelude::*;
 
 // ARBITRUM
-#[allow(dead_code)]
 pub(crate) const WETH_ARBI: &str = "0x82af49447d8a07e3bd95bd0d56f35241523fbab1";
-#[allow(dead_code)]
 pub(crate) const USDC_ARBI: &str = "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8";
+pub(crate) const USDT_ARBI: &str = "0xfd086bc7cd5c481dcc9c85ebe478a1c0b69fcbb9";
+pub(crate) const SUSHISWAPV2_WETH_USDC_ARBI: &str = "0x905dfcd5649217c42684f23958568e533c711aa3";
 
 pub(crate) const UNISWAPV3_FACTORY: &str = "0x1f98431c8ad98523631ae4a59f267346ea31f984";
 #[allow(dead_code)]
@@ -19,10 +19,11 @@ pub(crate) const TRADERJOE_FACTORY_ARBITRUM: &str = "0x7BFd7192E76D950832c77BB41
 
 
 // ETHEREUM
-#[allow(dead_code)]
 pub(crate) const USDC_MAINNET: &str = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
-#[allow(dead_code)]
+pub(crate) const USDT_MAINNET: &str = "0xdac17f958d2ee523a2206206994597c13d831ec7";
+pub(crate) const DAI_MAINNET: &str = "0x6b175474e89094c44da98b954eedeac495271d0f";
 pub(crate) const WETH_MAINNET: &str = "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+pub(crate) const UNISWAPV2_USDC_WETH: &str = "0xb4e16d0168e52d35cacd2c6185b44281ec28c9dc";
 
 pub(crate) const UNISWAPV2_FACTORY: &str = "0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f";
 #[allow(dead_code)]
@@ -35,24 +36,145 @@ pub(crate) const SUSHISWAPV2_ROUTER: &str = "0xd9e1ce17f2641f24ae83637ab66a2cca9
 pub(crate) const PANCAKESWAP_FACTORY: &str = "0x1097053fd2ea711dad45caccc45eff7548fcb362";
 
 // AVALANCHE
-#[allow(dead_code)]
 pub(crate) const WAVAX: &str = "0xb31f66aa3c1e785363f0875a1b74e27b85fd66c7";
+pub(crate) const USDC_AVAX: &str = "0xa7d7079b0fead91f3e65f86e8915cb59c1a4c664";
+pub(crate) const USDT_AVAX: &str = "0x9702230a8ea53601f5cd2dc00fdbc13d4df4a8c7";
+pub(crate) const TRADERJOE_WAVAX_USDC: &str = "0xa389f9430876455c36478deea9769b7ca4e3ddb1";
+
+// POLYGON
+pub(crate) const WMATIC: &str = "0x0d500b1d8e8ef31e21c99d1db9a6444d3adf1270";
+pub(crate) const USDC_POLYGON: &str = "0x2791bca1f2de4661ed88a30c99a7a9449aa84174";
+pub(crate) const USDT_POLYGON: &str = "0xc2132d05d31c914a87c6611c10748aeb04b58e8f";
+pub(crate) const QUICKSWAP_WMATIC_USDC: &str = "0x6e7a5fafcec6bb1e78bae2a1f0b612012bf14827";
 
 // BSC
+pub(crate) const WBNB: &str = "0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c";
+pub(crate) const BUSD_BSC: &str = "0xe9e7cea3dedca5984780bafc599bd69add087d56";
+pub(crate) const USDT_BSC: &str = "0x55d398326f99059ff775485246999027b3197955";
+pub(crate) const USDC_BSC: &str = "0x8ac76a51cc950d9822d68b83fe1ad97b32cd580d";
+pub(crate) const PANCAKESWAP_WBNB_BUSD: &str = "0x58f876857a02d6762e0101bb5c46a8c1ed44dc16";
+pub(crate) const PANCAKESWAP_FACTORY_BSC: &str = "0xca143ce32fe78f1f7019d7d551a6402fc5350c73";
 
 
 // FANTOM
+pub(crate) const WFTM: &str = "0x21be370d5312f44cb42ce377bc9b8a0cef1a4c83";
+pub(crate) const USDC_FANTOM: &str = "0x04068da6c83afcfa0e13ba15a6696662335d5b75";
+pub(crate) const USDT_FANTOM: &str = "0x049d68029688eabf473097a2fc38ef61633a3c7a";
+pub(crate) const SPOOKY_SWAP_WFTM_USDC: &str = "0x2b4c76d0dc16be1c31d4c1dc53bf9b45987fc75c";
 #[allow(dead_code)]
 pub(crate) const SPOOKY_SWAP_ROUTER: &str = "0xF491e7B69E4244ad4002BC14e878a34207E38c29";
-#[allow(dead_code)]
 pub(crate) const SPOOKY_SWAP_FACTORY: &str = "0x152eE697f2E276fA89E96742e9bB9aB1F2E61bE3";
 
 
 // CRONOS
+pub(crate) const WCRO: &str = "0x5c7f8a570d578ed84e63fdfa7b1ee72deae1ae23";
+pub(crate) const USDC_CRONOS: &str = "0xc21223249ca28397b4b6541dffaecc539bff0c59";
+pub(crate) const USDT_CRONOS: &str = "0x66e428c3f67a68878562e79a0234c1f83c208770";
+pub(crate) const VVS_WCRO_USDC: &str = "0xe61db569e231b3f5530168aa2c9d50246525b6d6";
 pub(crate) const VVS_FACTORY: &str = "0x3B44B2a187a7b3824131F8db5a74194D0a42Fc15";
 #[allow(dead_code)]
 pub(crate) const VVS_ROUTER: &str = "0x145863Eb42Cf62847A6Ca784e6416C1682b1b2Ae";
 
+/// Built-in metadata of the well known dexes, matched on the factory address.
+/// The values set on a `dex_contracts.json` entry take precedence.
+pub(crate) struct DexInfo {
+    pub factory: &'static str,
+    pub name: &'static str,
+    pub website: &'static str,
+}
+
+pub(crate) const KNOWN_DEXES: [DexInfo; 9] = [
+    DexInfo { factory: UNISWAPV2_FACTORY, name: "Uniswap V2", website: "https://app.uniswap.org" },
+    DexInfo { factory: UNISWAPV3_FACTORY, name: "Uniswap V3", website: "https://app.uniswap.org" },
+    DexInfo { factory: SUSHISWAPV2_FACTORY, name: "SushiSwap V2", website: "https://www.sushi.com" },
+    DexInfo { factory: SUSHIV2_FACTORY_ARBITRUM, name: "SushiSwap V2", website: "https://www.sushi.com" },
+    DexInfo { factory: PANCAKESWAP_FACTORY, name: "PancakeSwap V2", website: "https://pancakeswap.finance" },
+    DexInfo { factory: PANCAKESWAP_FACTORY_BSC, name: "PancakeSwap V2", website: "https://pancakeswap.finance" },
+    DexInfo { factory: TRADERJOE_FACTORY_ARBITRUM, name: "Trader Joe", website: "https://traderjoexyz.com" },
+    DexInfo { factory: SPOOKY_SWAP_FACTORY, name: "SpookySwap", website: "https://spooky.fi" },
+    DexInfo { factory: VVS_FACTORY, name: "VVS Finance", website: "https://vvs.finance" },
+];
+
+pub(crate) fn known_dex(factory: Address) -> Option<&'static DexInfo> {
+    KNOWN_DEXES
+        .iter()
+        .find(|dex| dex.factory.parse::<Address>().ok() == Some(factory))
+}
+
+/// Wrapped native / stablecoin pair used as the price reference of a chain.
+pub(crate) struct PriceReference {
+    pub pool: &'static str,
+    pub native: &'static str,
+}
+
+pub(crate) fn price_reference(chain: &str) -> Option<PriceReference> {
+    let (pool, native) = match chain {
+        "ETHEREUM" => (UNISWAPV2_USDC_WETH, WETH_MAINNET),
+        "ARBITRUM" => (SUSHISWAPV2_WETH_USDC_ARBI, WETH_ARBI),
+        "POLYGON" => (QUICKSWAP_WMATIC_USDC, WMATIC),
+        "BSC" => (PANCAKESWAP_WBNB_BUSD, WBNB),
+        "FANTOM" => (SPOOKY_SWAP_WFTM_USDC, WFTM),
+        "AVALANCHE" => (TRADERJOE_WAVAX_USDC, WAVAX),
+        "CRONOS" => (VVS_WCRO_USDC, WCRO),
+        _ => return None,
+    };
+    Some(PriceReference { pool, native })
+}
+
+/// LP locker families, the unlock date can only be read from some of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockerKind {
+    Unicrypt,
+    TeamFinance,
+    PinkLock,
+}
+
+/// Known LP locker contracts, matched on the chain and the address.
+pub struct LockerInfo {
+    pub chain: &'static str,
+    pub address: &'static str,
+    pub name: &'static str,
+    pub kind: LockerKind,
+}
+
+pub(crate) const KNOWN_LOCKERS: [LockerInfo; 5] = [
+    LockerInfo {
+        chain: "ETHEREUM",
+        address: "0x663a5c229c09b049e36dcc11a9b0d4a8eb9db214",
+        name: "Unicrypt",
+        kind: LockerKind::Unicrypt,
+    },
+    LockerInfo {
+        chain: "ETHEREUM",
+        address: "0xe2fe530c047f2d85298b07d9333c05737f1435fb",
+        name: "Team Finance",
+        kind: LockerKind::TeamFinance,
+    },
+    LockerInfo {
+        chain: "ETHEREUM",
+        address: "0x71b5759d73262fbb223956913ecf4ecc51057641",
+        name: "PinkLock",
+        kind: LockerKind::PinkLock,
+    },
+    LockerInfo {
+        chain: "BSC",
+        address: "0xc765bddb93b0d1c1a88282ba0fa6b2d00e3e0c83",
+        name: "Unicrypt",
+        kind: LockerKind::Unicrypt,
+    },
+    LockerInfo {
+        chain: "BSC",
+        address: "0x407993575c91ce7643a4d4ccacc9a98c36ee1bbe",
+        name: "PinkLock",
+        kind: LockerKind::PinkLock,
+    },
+];
+
+pub(crate) fn known_locker(chain: &str, address: Address) -> Option<&'static LockerInfo> {
+    KNOWN_LOCKERS
+        .iter()
+        .find(|locker| locker.chain == chain && locker.address.parse::<Address>().ok() == Some(address))
+}
 
 abigen!(UniV2Router, "src/abi/UniV2Router.json");
 abigen!(UniV2Factory, "src/abi/UniV2Factory.json");
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use eyre::Result;
+use webhook::models::{Embed, Message};
+
+use super::template::{Rendered, Templates};
+use super::{check_response, AlertSink, EventKind, LiquidityEvent};
+
+/// Discord webhook, `DISCORD_WEBHOOK_{CHAIN}`, mentioning `ROLE_ID_{CHAIN}` when set.
+/// The message is built with the `webhook` models but posted with `reqwest`, so
+/// the `retry_after` of a 429 reaches the delivery queue.
+pub struct DiscordSink {
+    client: reqwest::Client,
+    url: String,
+    role_id: Option<String>,
+    templates: Option<Arc<Templates>>,
+}
+
+impl DiscordSink {
+    pub const NAME: &'static str = "discord";
+
+    /// `None` when the chain has no webhook.
+    pub fn from_env(chain: &str, client: reqwest::Client, templates: Option<Arc<Templates>>) -> Result<Option<Self>> {
+        let chain_str = chain.to_uppercase();
+        let url = match std::env::var(format!("DISCORD_WEBHOOK_{}", chain_str)) {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        let role_id = std::env::var(format!("ROLE_ID_{}", chain_str))
+            .ok()
+            .filter(|role_id| !role_id.is_empty());
+        Ok(Some(Self { client, url, role_id, templates }))
+    }
+}
+
+#[async_trait]
+impl AlertSink for DiscordSink {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    async fn send(&self, event: &LiquidityEvent) -> Result<()> {
+        let content = match &self.role_id {
+            Some(role_id) => format!("<@&{}>", role_id),
+            None => String::new(),
+        };
+        let rendered = self
+            .templates
+            .as_ref()
+            .and_then(|templates| templates.render(Self::NAME, event, str::to_string));
+        let mut message = Message::new();
+        message
+            .content(content.as_str())
+            .username("Liquidity Alert Bot")
+            .embed(|embed| embed_event(embed, event, rendered.as_ref()));
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(serde_json::to_string(&message)?)
+            .send()
+            .await?;
+        check_response(response).await
+    }
+}
+
+/// The templated message replaces the title, description and fields of the embed.
+fn embed_event<'a>(embed: &'a mut Embed, event: &LiquidityEvent, rendered: Option<&Rendered>) -> &'a mut Embed {
+    match rendered {
+        Some(rendered) => {
+            embed.title(rendered.title.as_str()).description(rendered.body.as_str());
+        }
+        None => {
+            embed
+                .title(event.title().as_str())
+                .description(event.description().as_str());
+            for (name, value) in event.fields() {
+                embed.field(name.as_str(), value.as_str(), false);
+            }
+            embed.field("Chart", event.chart.as_str(), false);
+        }
+    }
+    match event.kind {
+        EventKind::Rug { .. } => { embed.color("15158332"); }
+        EventKind::Retracted { .. } => { embed.color("9807270"); }
+        _ => {}
+    }
+    if let Some(website) = &event.website { embed.url(website); }
+    if let Some(logo) = &event.logo { embed.thumbnail(logo); }
+    embed
+}
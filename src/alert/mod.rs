@@ -0,0 +1,771 @@
+//! Alerts on new liquidity: a `LiquidityEvent` built by the pairs and pools is
+//! fanned out by the `AlertDispatcher` of the chain to every configured `AlertSink`,
+//! through a delivery queue per sink.
+
+pub mod discord;
+mod queue;
+pub mod slack;
+pub mod telegram;
+pub mod template;
+pub mod webhook;
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use colored::*;
+use ethers::prelude::*;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::dex::Dex;
+use crate::filter::Launch;
+use crate::helpers::u256_to_float;
+use crate::lp::LpDistribution;
+use crate::mempool::PendingLiquidity;
+use crate::oracle::LiquidityValue;
+use crate::pair::Pair;
+use crate::pool::{Pool, Position};
+use crate::quote::{PairQuote, Side};
+use crate::risk::RiskReport;
+use crate::simulation::Simulation;
+use crate::store::Store;
+use crate::timestamp_print;
+use crate::token::Token;
+use crate::wallet::WalletProfile;
+
+use discord::DiscordSink;
+use queue::{spawn_worker, Delivery};
+use slack::SlackSink;
+use telegram::TelegramSink;
+use template::Templates;
+use webhook::WebhookSink;
+
+/// Names of the sinks, as used by the `sinks` of the filter rules.
+pub const SINKS: [&str; 4] = [DiscordSink::NAME, TelegramSink::NAME, WebhookSink::NAME, SlackSink::NAME];
+
+/// Alerted events kept to be retracted if their log is reorged out.
+const RETRACTABLE: usize = 256;
+/// Alert keys remembered to skip duplicates when there's no store, the oldest are forgotten.
+const DEDUP_KEYS: usize = 4096;
+
+/// Version of the JSON of a `LiquidityEvent`, bumped on breaking changes.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A destination of the alerts.
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    /// Name of the sink in the filter rules and the logs.
+    fn name(&self) -> &'static str;
+
+    /// Sends the event once, the delivery queue handles the retries. Return a
+    /// `SendFailure` to tell it how.
+    async fn send(&self, event: &LiquidityEvent) -> Result<()>;
+}
+
+/// A failed send the delivery queue should not retry with its usual backoff.
+#[derive(Debug)]
+pub enum SendFailure {
+    /// Rate limited, the sink asks to wait before sending again.
+    RetryAfter(Duration),
+    /// The sink refused the alert, sending it again won't help.
+    Rejected(String),
+}
+
+impl fmt::Display for SendFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendFailure::RetryAfter(wait) => write!(f, "rate limited for {}s", wait.as_secs_f64()),
+            SendFailure::Rejected(reason) => write!(f, "rejected: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SendFailure {}
+
+/// Turns the answer of an HTTP sink into a `SendFailure` when it failed. The delay
+/// of a 429 is read from the `Retry-After` header, or from the body like Discord
+/// (`retry_after`) and Telegram (`parameters.retry_after`) send it.
+pub(crate) async fn check_response(response: reqwest::Response) -> Result<()> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let header = response
+        .headers()
+        .get("Retry-After")
+        .and_then(|value| value.to_str().ok()?.parse::<f64>().ok());
+    let text = response.text().await.unwrap_or_default();
+    if status.as_u16() == 429 {
+        let body: Value = serde_json::from_str(&text).unwrap_or_default();
+        let seconds = header
+            .or_else(|| body["retry_after"].as_f64())
+            .or_else(|| body["parameters"]["retry_after"].as_f64())
+            .unwrap_or(1.0);
+        return Err(SendFailure::RetryAfter(Duration::from_secs_f64(seconds.max(0.0))).into());
+    }
+    if status.is_client_error() {
+        return Err(SendFailure::Rejected(format!("{}: {}", status, text)).into());
+    }
+    Err(eyre::eyre!("{}: {}", status, text))
+}
+
+/// What happened to the pair or pool.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EventKind {
+    /// First liquidity added to a pair.
+    Liquidity,
+    /// First position opened on a concentrated liquidity pool.
+    Position {
+        /// Fee tier in %.
+        fee: f64,
+        /// Price of token0 in token1.
+        price: f64,
+        owner: Address,
+        tick_lower: i32,
+        tick_upper: i32,
+        price_lower: f64,
+        price_upper: f64,
+    },
+    /// Liquidity removed past the rug threshold.
+    Rug {
+        /// Share of the launch liquidity removed (0 to 1).
+        pulled: f64,
+        minutes: u64,
+    },
+    /// Liquidity about to be added by a pending transaction.
+    Pending {
+        sender: Address,
+        /// Max fee per gas (or gas price) in gwei.
+        gas_price: Option<f64>,
+        /// Timestamp after which the router reverts the call.
+        deadline: u64,
+        /// The pair is created by the same transaction.
+        new_pair: bool,
+    },
+    /// The log behind an event already alerted was reorged out.
+    Retracted {
+        /// Kind of the retracted event.
+        event: String,
+    },
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Liquidity => "liquidity",
+            EventKind::Position { .. } => "position",
+            EventKind::Rug { .. } => "rug",
+            EventKind::Pending { .. } => "pending",
+            EventKind::Retracted { .. } => "retracted",
+        }
+    }
+}
+
+/// A token of the pair, with what was found about it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub address: Address,
+    pub name: String,
+    pub symbol: String,
+    /// `None` when the token was not checked on the explorer, or the check failed.
+    pub verified: Option<bool>,
+    pub simulation: Option<Simulation>,
+    pub risk: Option<RiskReport>,
+}
+
+impl TokenInfo {
+    fn new(token: &Token) -> Self {
+        Self {
+            address: token.address,
+            name: token.name.clone(),
+            symbol: token.symbol.clone(),
+            verified: token.verified,
+            simulation: token.simulation.clone(),
+            risk: token.risk.clone(),
+        }
+    }
+}
+
+/// Everything an alert needs to know about an event on a pair or pool.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LiquidityEvent {
+    #[serde(flatten)]
+    pub kind: EventKind,
+    pub chain: String,
+    pub dex: String,
+    pub website: Option<String>,
+    pub logo: Option<String>,
+    pub pair: Address,
+    pub chart: String,
+    pub token0: TokenInfo,
+    pub token1: TokenInfo,
+    /// Which token is launched against which quote asset.
+    pub listing: String,
+    /// Address of the launched token, when it can be told apart.
+    pub launched: Option<Address>,
+    /// Liquidity added, or removed for a rug, in token0 and token1.
+    pub amount0: f64,
+    pub amount1: f64,
+    /// Reserves of the pair after the event, in token0 and token1.
+    pub reserves: Option<(f64, f64)>,
+    pub value_usd: Option<f64>,
+    /// Transaction and block of the log behind the event.
+    pub tx_hash: Option<H256>,
+    pub block: Option<U64>,
+    pub deployer: Option<WalletProfile>,
+    pub lp: Option<LpDistribution>,
+    /// Filter rule that matched the launch.
+    pub rule: Option<String>,
+}
+
+impl LiquidityEvent {
+    fn new(kind: EventKind, dex: &Dex, pair: Address, token0: &Token, token1: &Token, quote: PairQuote) -> Self {
+        let launched = quote.base().map(|side| match side {
+            Side::Token0 => token0.address,
+            Side::Token1 => token1.address,
+        });
+        Self {
+            kind,
+            chain: dex.chain.clone(),
+            dex: dex.name.clone(),
+            website: dex.website.clone(),
+            logo: dex.logo.clone(),
+            pair,
+            chart: dex.chart_url(pair),
+            token0: TokenInfo::new(token0),
+            token1: TokenInfo::new(token1),
+            listing: quote.describe(token0, token1),
+            launched,
+            amount0: 0.0,
+            amount1: 0.0,
+            reserves: None,
+            value_usd: None,
+            tx_hash: None,
+            block: None,
+            deployer: None,
+            lp: None,
+            rule: None,
+        }
+    }
+
+    /// First liquidity added to a pair.
+    pub fn liquidity(pair: &Pair, amount0: f64, amount1: f64, value: Option<LiquidityValue>, lp: Option<LpDistribution>) -> Self {
+        Self {
+            amount0,
+            amount1,
+            reserves: Some(pair.reserve_amounts()),
+            value_usd: value.map(|value| value.total()),
+            deployer: pair.deployer.clone(),
+            lp,
+            ..Self::new(EventKind::Liquidity, &pair.dex, pair.address, &pair.token0, &pair.token1, pair.quote)
+        }
+    }
+
+    /// First position opened on a pool.
+    pub fn position(pool: &Pool, position: &Position, value: Option<LiquidityValue>) -> Self {
+        let kind = EventKind::Position {
+            fee: pool.fee as f64 / 10_000.0,
+            price: pool.price(),
+            owner: position.owner,
+            tick_lower: position.tick_lower,
+            tick_upper: position.tick_upper,
+            price_lower: pool.price_at_tick(position.tick_lower),
+            price_upper: pool.price_at_tick(position.tick_upper),
+        };
+        Self {
+            amount0: position.amount0,
+            amount1: position.amount1,
+            value_usd: value.map(|value| value.total()),
+            deployer: pool.deployer.clone(),
+            ..Self::new(kind, &pool.dex, pool.address, &pool.token0, &pool.token1, pool.quote)
+        }
+    }
+
+    /// Liquidity about to be added to `pair` by a pending transaction.
+    pub fn pending(launch: &Launch, pair: Address, pending: &PendingLiquidity, new_pair: bool) -> Self {
+        let kind = EventKind::Pending {
+            sender: pending.sender,
+            gas_price: pending.gas_price.map(|price| u256_to_float(price) / 1e9),
+            deadline: pending.deadline.low_u64(),
+            new_pair,
+        };
+        Self {
+            amount0: launch.amounts.0,
+            amount1: launch.amounts.1,
+            value_usd: launch.value.map(|value| value.total()),
+            tx_hash: Some(pending.hash),
+            ..Self::new(kind, launch.dex, pair, launch.token0, launch.token1, launch.quote)
+        }
+    }
+
+    /// Liquidity pulled from a pair, `reserves` being what is left.
+    pub fn rug(pair: &Pair, pulled: f64, amount0: f64, amount1: f64, reserves: (f64, f64), minutes: u64) -> Self {
+        Self {
+            amount0,
+            amount1,
+            reserves: Some(reserves),
+            deployer: pair.deployer.clone(),
+            ..Self::new(EventKind::Rug { pulled, minutes }, &pair.dex, pair.address, &pair.token0, &pair.token1, pair.quote)
+        }
+    }
+
+    /// Sets the transaction and block of the log behind the event.
+    pub fn with_log(mut self, meta: &LogMeta) -> Self {
+        self.tx_hash = Some(meta.transaction_hash);
+        self.block = Some(meta.block_number);
+        self
+    }
+
+    pub fn with_rule(mut self, rule: Option<String>) -> Self {
+        self.rule = rule;
+        self
+    }
+
+    /// Identifies the event of a pair, an alert is sent once per key and sink.
+    pub fn key(&self) -> String {
+        match &self.kind {
+            EventKind::Retracted { event } => format!(
+                "{:#x}:retracted:{}:{:#x}",
+                self.pair,
+                event,
+                self.tx_hash.unwrap_or_default()
+            ),
+            // Several pending additions can target the same pair, only one gets mined.
+            EventKind::Pending { .. } => format!("{:#x}:pending", self.tx_hash.unwrap_or_default()),
+            kind => format!("{:#x}:{}", self.pair, kind.name()),
+        }
+    }
+
+    /// The retraction of this event, once its log was reorged out.
+    pub fn retraction(&self) -> Self {
+        Self {
+            kind: EventKind::Retracted {
+                event: self.kind.name().to_string(),
+            },
+            ..self.clone()
+        }
+    }
+
+    /// The launched token, token0 when it can't be told apart.
+    pub fn launched_token(&self) -> &TokenInfo {
+        if self.launched == Some(self.token1.address) {
+            &self.token1
+        } else {
+            &self.token0
+        }
+    }
+
+    /// Explorer page of an address, e.g. `explorer_url("token", address)`.
+    pub fn explorer_url(&self, page: &str, address: Address) -> String {
+        format!("https://{}{}/{:#x}", Token::get_explorer(self.chain.clone()), page, address)
+    }
+
+    pub fn title(&self) -> String {
+        match self.kind {
+            EventKind::Rug { .. } => format!("{} - Liquidity pulled", self.dex),
+            EventKind::Pending { .. } => format!("{} - Pending launch", self.dex),
+            EventKind::Retracted { .. } => format!("{} - Reorged out", self.dex),
+            _ => self.dex.clone(),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        let (token0, token1) = (&self.token0.symbol, &self.token1.symbol);
+        match &self.kind {
+            EventKind::Liquidity => format!("Liquidity added on pair {}/{}", token0, token1),
+            EventKind::Position { fee, .. } => format!("Position opened on pool {}/{} ({}%)", token0, token1, fee),
+            EventKind::Rug { pulled, minutes } => format!(
+                "{:.1}% of the liquidity removed from pair {}/{} {} minutes after launch",
+                pulled * 100.0,
+                token0,
+                token1,
+                minutes
+            ),
+            EventKind::Pending { new_pair, .. } => format!(
+                "Liquidity about to be added to {}pair {}/{}",
+                if *new_pair { "new " } else { "" },
+                token0,
+                token1
+            ),
+            EventKind::Retracted { event } => format!(
+                "The {} alert of pair {}/{} is void, its transaction was removed from block {} by a chain reorganization",
+                event,
+                token0,
+                token1,
+                self.block.unwrap_or_default()
+            ),
+        }
+    }
+
+    /// Named values shown by the chat alerts, the chart link aside.
+    pub fn fields(&self) -> Vec<(String, String)> {
+        let (token0, token1) = (&self.token0.symbol, &self.token1.symbol);
+        let mut fields = Vec::new();
+        match self.kind {
+            EventKind::Rug { .. } => {
+                fields.push(("Removed".to_string(), format!("{} {} | {} {}", self.amount0, token0, self.amount1, token1)));
+                fields.push(("Pair".to_string(), format!("{:#066x}", self.pair)));
+                return fields;
+            }
+            EventKind::Retracted { .. } => {
+                fields.push(("Pair".to_string(), format!("{:#066x}", self.pair)));
+                if let Some(tx_hash) = self.tx_hash {
+                    fields.push(("Transaction".to_string(), format!("{:#066x}", tx_hash)));
+                }
+                return fields;
+            }
+            _ => {}
+        }
+        fields.push(("AmountIn".to_string(), format!("{} {}", self.amount0, token0)));
+        fields.push(("AmountOut".to_string(), format!("{} {}", self.amount1, token1)));
+        fields.push(("Listing".to_string(), self.listing.clone()));
+        if let EventKind::Pending { sender, gas_price, deadline, .. } = &self.kind {
+            fields.push(("Sender".to_string(), format!("{:#x}", sender)));
+            if let Some(gas_price) = gas_price {
+                fields.push(("Gas price".to_string(), format!("{:.2} gwei", gas_price)));
+            }
+            let deadline = Utc
+                .timestamp_opt(*deadline as i64, 0)
+                .single()
+                .map(|date| date.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                .unwrap_or_else(|| deadline.to_string());
+            fields.push(("Deadline".to_string(), deadline));
+            if let Some(tx_hash) = self.tx_hash {
+                fields.push(("Transaction".to_string(), format!("{:#066x}", tx_hash)));
+            }
+        }
+        if let EventKind::Position { price, tick_lower, tick_upper, price_lower, price_upper, .. } = &self.kind {
+            fields.push(("Price".to_string(), format!("{} {} per {}", price, token1, token0)));
+            fields.push((
+                "Range".to_string(),
+                format!("[{}, {}] {} per {} (ticks {} / {})", price_lower, price_upper, token1, token0, tick_lower, tick_upper),
+            ));
+        }
+        if let Some(value) = self.value_usd {
+            fields.push(("Liquidity".to_string(), format!("${:.2}", value)));
+        }
+        for token in [&self.token0, &self.token1] {
+            if let Some(verified) = token.verified {
+                fields.push((format!("Verified {}", token.symbol), if verified { "yes" } else { "no" }.to_string()));
+            }
+            if let Some(simulation) = &token.simulation {
+                fields.push((format!("Simulation {}", token.symbol), simulation.to_string()));
+            }
+        }
+        if let Some(deployer) = &self.deployer {
+            fields.push(("Deployer".to_string(), deployer.to_string()));
+        }
+        for token in [&self.token0, &self.token1] {
+            if let Some(risk) = &token.risk {
+                fields.push((format!("Risk {}", token.symbol), risk.to_string()));
+            }
+        }
+        if let Some(rule) = &self.rule {
+            fields.push(("Rule".to_string(), rule.clone()));
+        }
+        fields
+    }
+}
+
+/// Which sinks an event goes to.
+#[derive(Clone, Debug, Default)]
+pub struct Route {
+    /// Filter rule that let the event through.
+    pub rule: Option<String>,
+    /// Sinks named by the rule, every sink of the chain when `None`.
+    pub sinks: Option<Vec<String>>,
+}
+
+impl Route {
+    fn includes(&self, sink: &str) -> bool {
+        match &self.sinks {
+            Some(sinks) => sinks.iter().any(|name| name == sink),
+            None => true,
+        }
+    }
+}
+
+/// The last `DEDUP_KEYS` keys inserted, so the dedup set of a long running
+/// process stays bounded.
+#[derive(Default)]
+struct RecentKeys {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+}
+
+impl RecentKeys {
+    /// False when the key is already there.
+    fn insert(&mut self, key: String) -> bool {
+        if !self.keys.insert(key.clone()) {
+            return false;
+        }
+        if self.order.len() == DEDUP_KEYS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        self.order.push_back(key);
+        true
+    }
+
+    fn remove(&mut self, key: &str) {
+        if self.keys.remove(key) {
+            self.order.retain(|kept| kept != key);
+        }
+    }
+}
+
+/// Queues the alerts of a chain for its sinks. Without `--db` the queue only lives
+/// in memory, with it the undelivered alerts are sent again on the next start.
+pub struct AlertDispatcher {
+    chain: String,
+    sinks: Vec<(Arc<dyn AlertSink>, UnboundedSender<Delivery>)>,
+    store: Option<Arc<Store>>,
+    /// `sink:key` of the alerts already queued, when there's no store to tell.
+    queued: Mutex<RecentKeys>,
+    /// Recent events with the sinks they were queued for.
+    sent: Mutex<VecDeque<(Arc<LiquidityEvent>, Vec<&'static str>)>>,
+}
+
+impl fmt::Debug for AlertDispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlertDispatcher")
+            .field("chain", &self.chain)
+            .field("sinks", &self.names())
+            .finish()
+    }
+}
+
+impl AlertDispatcher {
+    /// Sinks configured in the env for the chain (see `.env.example`), each sending at
+    /// most `rate` messages per minute. The alerts left undelivered in the store are
+    /// queued again.
+    pub fn for_chain(chain: &str, rate: u32, templates: Option<Arc<Templates>>, store: Option<Arc<Store>>) -> Result<Self> {
+        let client = reqwest::Client::new();
+        let mut sinks: Vec<Arc<dyn AlertSink>> = Vec::new();
+        if let Some(discord) = DiscordSink::from_env(chain, client.clone(), templates.clone())? {
+            sinks.push(Arc::new(discord));
+        }
+        if let Some(telegram) = TelegramSink::from_env(chain, client.clone(), templates.clone())? {
+            sinks.push(Arc::new(telegram));
+        }
+        if let Some(webhook) = WebhookSink::from_env(chain, client.clone())? {
+            sinks.push(Arc::new(webhook));
+        }
+        if let Some(slack) = SlackSink::from_env(chain, client, templates)? {
+            sinks.push(Arc::new(slack));
+        }
+
+        let dispatcher = Self {
+            chain: chain.to_string(),
+            sinks: sinks
+                .into_iter()
+                .map(|sink| {
+                    let worker = spawn_worker(chain.to_string(), sink.clone(), rate, store.clone());
+                    (sink, worker)
+                })
+                .collect(),
+            store,
+            queued: Mutex::new(RecentKeys::default()),
+            sent: Mutex::new(VecDeque::new()),
+        };
+        dispatcher.resume()?;
+        Ok(dispatcher)
+    }
+
+    /// A dispatcher without sinks, when alerts are off.
+    pub fn disabled(chain: &str) -> Self {
+        Self {
+            chain: chain.to_string(),
+            sinks: Vec::new(),
+            store: None,
+            queued: Mutex::new(RecentKeys::default()),
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Names of the sinks, for the startup logs.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.sinks.iter().map(|(sink, _)| sink.name()).collect()
+    }
+
+    /// Queues the undelivered alerts of the store.
+    fn resume(&self) -> Result<()> {
+        let store = match &self.store {
+            Some(store) => store,
+            None => return Ok(()),
+        };
+        let mut resumed = 0;
+        for record in store.pending_alerts(&self.chain)? {
+            let worker = self.sinks.iter().find(|(sink, _)| sink.name() == record.sink);
+            match (worker, serde_json::from_str::<LiquidityEvent>(&record.payload)) {
+                (Some((_, worker)), Ok(event)) => {
+                    let _ = worker.send(Delivery {
+                        id: Some(record.id),
+                        event: Arc::new(event),
+                    });
+                    resumed += 1;
+                }
+                // Kept until the sink is configured again.
+                (None, _) => {}
+                (_, Err(err)) => {
+                    timestamp_print!(
+                        Color::Red,
+                        Some(false),
+                        self.chain,
+                        format!("[ALERT] Undelivered alert {} can't be read, dropped: {}", record.id, err)
+                    );
+                    store.alert_delivered(record.id)?;
+                }
+            }
+        }
+        if resumed > 0 {
+            timestamp_print!(
+                Color::Yellow,
+                Some(false),
+                self.chain,
+                format!("[ALERT] {} undelivered alerts queued again", resumed)
+            );
+        }
+        Ok(())
+    }
+
+    /// Queues the event for every sink of the route, once per pair and kind of event.
+    pub fn dispatch(&self, event: LiquidityEvent, route: &Route) {
+        let event = Arc::new(event.with_rule(route.rule.clone()));
+        let key = event.key();
+        let mut queued = Vec::new();
+        let payload = match &self.store {
+            Some(_) => serde_json::to_string(event.as_ref()).ok(),
+            None => None,
+        };
+        for (sink, worker) in self.sinks.iter().filter(|(sink, _)| route.includes(sink.name())) {
+            let id = match (&self.store, &payload) {
+                (Some(store), Some(payload)) => match store.queue_alert(&self.chain, sink.name(), &key, payload) {
+                    Ok(Some(id)) => Some(id),
+                    Ok(None) => continue,
+                    Err(err) => {
+                        timestamp_print!(Color::Red, Some(false), self.chain, format!("[DB] {:#}", err));
+                        None
+                    }
+                },
+                _ => {
+                    if !self.queued.lock().unwrap().insert(format!("{}:{}", sink.name(), key)) {
+                        continue;
+                    }
+                    None
+                }
+            };
+            let _ = worker.send(Delivery {
+                id,
+                event: event.clone(),
+            });
+            queued.push(sink.name());
+        }
+        if queued.is_empty() || matches!(event.kind, EventKind::Retracted { .. }) {
+            return;
+        }
+        let mut sent = self.sent.lock().unwrap();
+        if sent.len() == RETRACTABLE {
+            sent.pop_front();
+        }
+        sent.push_back((event, queued));
+    }
+
+    /// Sends a retraction to the sinks alerted of an event of the pair from that
+    /// transaction, and forgets the alert so it is sent again if the event comes
+    /// back in another block. Returns the kinds of the events retracted.
+    pub fn retract(&self, pair: Address, tx_hash: H256) -> Vec<&'static str> {
+        self.retract_matching(|event| event.pair == pair && event.tx_hash == Some(tx_hash))
+    }
+
+    /// Retracts every event alerted for the pair, whose creation was reorged out.
+    pub fn retract_pair(&self, pair: Address) -> Vec<&'static str> {
+        self.retract_matching(|event| event.pair == pair)
+    }
+
+    fn retract_matching(&self, matches: impl Fn(&LiquidityEvent) -> bool) -> Vec<&'static str> {
+        let retracted: VecDeque<(Arc<LiquidityEvent>, Vec<&'static str>)> = {
+            let mut sent = self.sent.lock().unwrap();
+            let (retracted, kept) = sent.drain(..).partition(|(event, _)| matches(event));
+            *sent = kept;
+            retracted
+        };
+        let mut kinds = Vec::new();
+        for (event, sinks) in retracted {
+            let key = event.key();
+            match &self.store {
+                Some(store) => {
+                    if let Err(err) = store.forget_alert(&self.chain, &key) {
+                        timestamp_print!(Color::Red, Some(false), self.chain, format!("[DB] {:#}", err));
+                    }
+                }
+                None => {
+                    let mut queued = self.queued.lock().unwrap();
+                    for sink in &sinks {
+                        queued.remove(&format!("{}:{}", sink, key));
+                    }
+                }
+            }
+            let route = Route {
+                rule: event.rule.clone(),
+                sinks: Some(sinks.iter().map(|sink| sink.to_string()).collect()),
+            };
+            self.dispatch(event.retraction(), &route);
+            kinds.push(event.kind.name());
+        }
+        kinds
+    }
+}
+
+/// A "liquidity pulled" event, for the tests of the sinks and the delivery queue.
+#[cfg(test)]
+pub(crate) fn rug_event() -> LiquidityEvent {
+    serde_json::from_value(serde_json::json!({
+        "kind": "rug",
+        "pulled": 0.5,
+        "minutes": 12,
+        "chain": "ETHEREUM",
+        "dex": "Uniswap V2",
+        "pair": "0x0000000000000000000000000000000000000011",
+        "chart": "https://dexscreener.com/ethereum/0x0000000000000000000000000000000000000011",
+        "token0": { "address": "0x00000000000000000000000000000000000000aa", "name": "Token", "symbol": "TKN" },
+        "token1": { "address": "0x00000000000000000000000000000000000000bb", "name": "Wrapped Ether", "symbol": "WETH" },
+        "listing": "TKN launched against WETH",
+        "amount0": 1.5,
+        "amount1": 2.0,
+    }))
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgets_the_oldest_keys() {
+        let mut keys = RecentKeys::default();
+        for index in 0..DEDUP_KEYS {
+            assert!(keys.insert(format!("discord:{}", index)));
+        }
+        assert!(!keys.insert("discord:0".to_string()));
+        assert!(keys.insert("discord:new".to_string()));
+        assert_eq!(keys.keys.len(), DEDUP_KEYS);
+        assert!(keys.insert("discord:0".to_string()), "the oldest key is forgotten");
+
+        keys.remove("discord:new");
+        assert!(keys.insert("discord:new".to_string()));
+        assert_eq!((keys.keys.len(), keys.order.len()), (DEDUP_KEYS, DEDUP_KEYS));
+    }
+}
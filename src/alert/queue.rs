@@ -0,0 +1,235 @@
+//! Delivery of the alerts: each sink has a worker sending its alerts in order, at
+//! most `rate` per minute, and retrying the failed ones with an exponential backoff.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use colored::*;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::store::Store;
+use crate::timestamp_print;
+
+use super::{AlertSink, LiquidityEvent, SendFailure};
+
+const MAX_ATTEMPTS: u32 = 8;
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+const MINUTE: Duration = Duration::from_secs(60);
+
+/// An alert waiting for its sink, `id` is its row in the store.
+pub(super) struct Delivery {
+    pub id: Option<i64>,
+    pub event: Arc<LiquidityEvent>,
+}
+
+/// Starts the worker of a sink, the alerts to deliver are sent on the returned channel.
+pub(super) fn spawn_worker(
+    chain: String,
+    sink: Arc<dyn AlertSink>,
+    rate: u32,
+    store: Option<Arc<Store>>,
+) -> UnboundedSender<Delivery> {
+    let (sender, receiver) = unbounded_channel();
+    let worker = Worker {
+        chain,
+        sink,
+        rate: rate.max(1) as usize,
+        store,
+        sent: VecDeque::new(),
+    };
+    tokio::spawn(worker.run(receiver));
+    sender
+}
+
+struct Worker {
+    chain: String,
+    sink: Arc<dyn AlertSink>,
+    /// Messages per minute.
+    rate: usize,
+    store: Option<Arc<Store>>,
+    /// When the messages of the last minute were sent.
+    sent: VecDeque<Instant>,
+}
+
+impl Worker {
+    async fn run(mut self, mut receiver: UnboundedReceiver<Delivery>) {
+        while let Some(delivery) = receiver.recv().await {
+            self.deliver(delivery).await;
+        }
+    }
+
+    /// Waits until a message can be sent without going over the rate.
+    async fn throttle(&mut self) {
+        loop {
+            while self.sent.front().is_some_and(|sent| sent.elapsed() >= MINUTE) {
+                self.sent.pop_front();
+            }
+            match self.sent.front() {
+                Some(oldest) if self.sent.len() >= self.rate => {
+                    tokio::time::sleep(MINUTE.saturating_sub(oldest.elapsed())).await;
+                }
+                _ => break,
+            }
+        }
+        self.sent.push_back(Instant::now());
+    }
+
+    async fn deliver(&mut self, delivery: Delivery) {
+        let name = self.sink.name();
+        let mut delay = FIRST_RETRY_DELAY;
+        for attempt in 1..=MAX_ATTEMPTS {
+            self.throttle().await;
+            let err = match self.sink.send(&delivery.event).await {
+                Ok(()) => return self.done(delivery.id),
+                Err(err) => err,
+            };
+            let wait = match err.downcast_ref::<SendFailure>() {
+                Some(SendFailure::Rejected(_)) => {
+                    timestamp_print!(
+                        Color::Red,
+                        Some(false),
+                        self.chain,
+                        format!("[ALERT][{}] {}, dropped", name, err)
+                    );
+                    return self.done(delivery.id);
+                }
+                Some(SendFailure::RetryAfter(wait)) => *wait,
+                None => {
+                    let wait = delay;
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                    wait
+                }
+            };
+            if attempt < MAX_ATTEMPTS {
+                timestamp_print!(
+                    Color::Yellow,
+                    Some(false),
+                    self.chain,
+                    format!("[ALERT][{}] Attempt {} failed: {}, retrying in {}s", name, attempt, err, wait.as_secs_f64())
+                );
+                tokio::time::sleep(wait).await;
+            }
+        }
+        timestamp_print!(
+            Color::Red,
+            Some(false),
+            self.chain,
+            format!(
+                "[ALERT][{}] Giving up after {} attempts{}",
+                name,
+                MAX_ATTEMPTS,
+                if delivery.id.is_some() { ", sent again on the next start" } else { "" }
+            )
+        );
+    }
+
+    fn done(&self, id: Option<i64>) {
+        if let (Some(store), Some(id)) = (&self.store, id) {
+            if let Err(err) = store.alert_delivered(id) {
+                timestamp_print!(Color::Red, Some(false), self.chain, format!("[DB] {:#}", err));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use eyre::{eyre, Result};
+
+    use super::*;
+    use crate::alert::rug_event;
+
+    /// Sink failing with the given errors, in order, then succeeding.
+    struct FlakySink {
+        failures: Mutex<VecDeque<eyre::Report>>,
+        attempts: AtomicU32,
+    }
+
+    impl FlakySink {
+        fn new(failures: Vec<eyre::Report>) -> Arc<Self> {
+            Arc::new(Self {
+                failures: Mutex::new(failures.into()),
+                attempts: AtomicU32::new(0),
+            })
+        }
+
+        fn attempts(&self) -> u32 {
+            self.attempts.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl AlertSink for FlakySink {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+
+        async fn send(&self, _event: &LiquidityEvent) -> Result<()> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            match self.failures.lock().unwrap().pop_front() {
+                Some(err) => Err(err),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Delivers one alert to `sink`, returns how long it took.
+    async fn deliver(sink: Arc<FlakySink>) -> Duration {
+        let mut worker = Worker {
+            chain: "TEST".to_string(),
+            sink,
+            rate: 60,
+            store: None,
+            sent: VecDeque::new(),
+        };
+        let start = tokio::time::Instant::now();
+        worker
+            .deliver(Delivery {
+                id: None,
+                event: Arc::new(rug_event()),
+            })
+            .await;
+        start.elapsed()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_with_a_doubling_delay() {
+        let sink = FlakySink::new(vec![eyre!("timeout"), eyre!("timeout"), eyre!("502 Bad Gateway")]);
+        let elapsed = deliver(sink.clone()).await;
+        assert_eq!(sink.attempts(), 4);
+        assert_eq!(elapsed.as_secs(), 2 + 4 + 8);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_the_delay_asked_by_the_sink() {
+        let sink = FlakySink::new(vec![
+            SendFailure::RetryAfter(Duration::from_secs(30)).into(),
+            eyre!("timeout"),
+        ]);
+        let elapsed = deliver(sink.clone()).await;
+        assert_eq!(sink.attempts(), 3);
+        // A rate limit doesn't double the backoff of the next failure.
+        assert_eq!(elapsed.as_secs(), 30 + 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_a_rejected_alert() {
+        let sink = FlakySink::new(vec![SendFailure::Rejected("400 Bad Request".to_string()).into()]);
+        let elapsed = deliver(sink.clone()).await;
+        assert_eq!(sink.attempts(), 1);
+        assert_eq!(elapsed, Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_the_last_attempt() {
+        let sink = FlakySink::new((0..MAX_ATTEMPTS + 1).map(|_| eyre!("timeout")).collect());
+        deliver(sink.clone()).await;
+        assert_eq!(sink.attempts(), MAX_ATTEMPTS);
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use eyre::Result;
+use serde_json::{json, Value};
+
+use super::template::{Rendered, Templates};
+use super::{check_response, AlertSink, LiquidityEvent};
+
+/// Slack incoming webhook, `SLACK_WEBHOOK_{CHAIN}`, posting Block Kit messages.
+pub struct SlackSink {
+    client: reqwest::Client,
+    url: String,
+    templates: Option<Arc<Templates>>,
+}
+
+impl SlackSink {
+    pub const NAME: &'static str = "slack";
+
+    /// `None` when the chain has no Slack webhook.
+    pub fn from_env(chain: &str, client: reqwest::Client, templates: Option<Arc<Templates>>) -> Result<Option<Self>> {
+        match std::env::var(format!("SLACK_WEBHOOK_{}", chain.to_uppercase())) {
+            Ok(url) if !url.is_empty() => Ok(Some(Self { client, url, templates })),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[async_trait]
+impl AlertSink for SlackSink {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    async fn send(&self, event: &LiquidityEvent) -> Result<()> {
+        let rendered = self
+            .templates
+            .as_ref()
+            .and_then(|templates| templates.render(Self::NAME, event, escape));
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(blocks(event, rendered.as_ref()).to_string())
+            .send()
+            .await?;
+        check_response(response).await
+    }
+}
+
+/// The fields of the Discord embed as Block Kit: a header, the description, the
+/// fields and buttons linking the chart and the explorer. A templated message
+/// replaces the header, description and fields.
+fn blocks(event: &LiquidityEvent, rendered: Option<&Rendered>) -> Value {
+    let (title, mut blocks) = match rendered {
+        Some(rendered) => (
+            rendered.title.clone(),
+            vec![
+                json!({ "type": "header", "text": { "type": "plain_text", "text": rendered.title } }),
+                json!({ "type": "section", "text": { "type": "mrkdwn", "text": rendered.body } }),
+            ],
+        ),
+        None => (event.title(), default_blocks(event)),
+    };
+    let token = event.launched_token();
+    blocks.push(json!({
+        "type": "actions",
+        "elements": [
+            { "type": "button", "text": { "type": "plain_text", "text": "Chart" }, "url": event.chart },
+            { "type": "button", "text": { "type": "plain_text", "text": format!("{} on explorer", token.symbol) }, "url": event.explorer_url("token", token.address) },
+            { "type": "button", "text": { "type": "plain_text", "text": "Pair" }, "url": event.explorer_url("address", event.pair) },
+        ]
+    }));
+    json!({
+        // Shown in the notifications.
+        "text": format!("{}: {}", title, event.description()),
+        "blocks": blocks,
+    })
+}
+
+fn default_blocks(event: &LiquidityEvent) -> Vec<Value> {
+    let mut blocks = vec![
+        json!({ "type": "header", "text": { "type": "plain_text", "text": event.title() } }),
+        json!({ "type": "section", "text": { "type": "mrkdwn", "text": escape(&event.description()) } }),
+    ];
+    let fields: Vec<Value> = event
+        .fields()
+        .iter()
+        .map(|(name, value)| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", escape(name), escape(value)) }))
+        .collect();
+    // A section holds at most 10 fields.
+    for fields in fields.chunks(10) {
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+    blocks
+}
+
+/// Escapes the characters Slack reserves in mrkdwn.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alert::{rug_event, EventKind};
+    use crate::risk::RiskReport;
+    use crate::simulation::Simulation;
+
+    /// A launch with every optional field set, 11 fields in all.
+    fn launch_event() -> LiquidityEvent {
+        let mut event = rug_event();
+        event.kind = EventKind::Liquidity;
+        event.value_usd = Some(1234.5);
+        event.rule = Some("liquidity > 1000 && verified".to_string());
+        for token in [&mut event.token0, &mut event.token1] {
+            token.verified = Some(true);
+            token.simulation = Some(Simulation::default());
+            token.risk = Some(RiskReport::default());
+        }
+        event
+    }
+
+    #[test]
+    fn chunks_the_fields_by_ten() {
+        let event = launch_event();
+        let payload = blocks(&event, None);
+        let blocks = payload["blocks"].as_array().unwrap();
+
+        let types: Vec<&str> = blocks.iter().map(|block| block["type"].as_str().unwrap()).collect();
+        assert_eq!(types, ["header", "section", "section", "section", "actions"]);
+        assert_eq!(blocks[2]["fields"].as_array().unwrap().len(), 10);
+        assert_eq!(blocks[3]["fields"].as_array().unwrap().len(), 1);
+        assert_eq!(blocks[2]["fields"][0]["text"], "*AmountIn*\n1.5 TKN");
+    }
+
+    #[test]
+    fn escapes_the_mrkdwn() {
+        assert_eq!(escape("<a> & <b>"), "&lt;a&gt; &amp; &lt;b&gt;");
+
+        let event = launch_event();
+        let payload = blocks(&event, None);
+        assert_eq!(
+            payload["blocks"][3]["fields"][0]["text"],
+            "*Rule*\nliquidity &gt; 1000 &amp;&amp; verified"
+        );
+        // The header is plain text, shown as is.
+        let mut event = rug_event();
+        event.dex = "Dex <V2>".to_string();
+        assert_eq!(blocks(&event, None)["blocks"][0]["text"]["text"], "Dex <V2> - Liquidity pulled");
+    }
+
+    #[test]
+    fn links_the_chart_and_explorer() {
+        let payload = blocks(&rug_event(), None);
+        let actions = payload["blocks"].as_array().unwrap().last().unwrap();
+        let buttons: Vec<(&str, &str)> = actions["elements"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|button| (button["text"]["text"].as_str().unwrap(), button["url"].as_str().unwrap()))
+            .collect();
+
+        assert_eq!(
+            buttons,
+            [
+                ("Chart", "https://dexscreener.com/ethereum/0x0000000000000000000000000000000000000011"),
+                ("TKN on explorer", "https://etherscan.io/token/0x00000000000000000000000000000000000000aa"),
+                ("Pair", "https://etherscan.io/address/0x0000000000000000000000000000000000000011"),
+            ]
+        );
+        assert!(payload["text"].as_str().unwrap().starts_with("Uniswap V2 - Liquidity pulled: 50.0% of the liquidity"));
+    }
+}
@@ -0,0 +1,248 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use eyre::{Result, WrapErr};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use super::template::Templates;
+use super::{check_response, AlertSink, LiquidityEvent};
+
+const DEFAULT_API_URL: &str = "https://api.telegram.org";
+/// Telegram allows about one message per second in a chat, and 20 per minute in a group.
+const CHAT_INTERVAL: Duration = Duration::from_secs(1);
+const GROUP_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Telegram bot, `TELEGRAM_BOT_TOKEN` posting to `TELEGRAM_CHAT_ID_{CHAIN}`.
+/// `TELEGRAM_API_URL` points it to another Bot API server, e.g. a local mock.
+pub struct TelegramSink {
+    client: reqwest::Client,
+    /// `{api}/bot{token}`
+    endpoint: String,
+    chat_id: String,
+    interval: Duration,
+    /// When the last message was sent, also serializes the messages of the chat.
+    last_sent: Mutex<Option<Instant>>,
+    templates: Option<Arc<Templates>>,
+}
+
+impl TelegramSink {
+    pub const NAME: &'static str = "telegram";
+
+    /// `None` when the chain has no chat.
+    pub fn from_env(chain: &str, client: reqwest::Client, templates: Option<Arc<Templates>>) -> Result<Option<Self>> {
+        let chat_env = format!("TELEGRAM_CHAT_ID_{}", chain.to_uppercase());
+        let chat_id = match std::env::var(&chat_env) {
+            Ok(chat_id) if !chat_id.is_empty() => chat_id,
+            _ => return Ok(None),
+        };
+        let token = std::env::var("TELEGRAM_BOT_TOKEN")
+            .wrap_err_with(|| format!("{} is set but TELEGRAM_BOT_TOKEN is missing", chat_env))?;
+        let api_url = std::env::var("TELEGRAM_API_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_API_URL.to_string());
+        // Group and channel ids are negative.
+        let interval = if chat_id.starts_with('-') { GROUP_INTERVAL } else { CHAT_INTERVAL };
+        Ok(Some(Self {
+            client,
+            endpoint: format!("{}/bot{}", api_url.trim_end_matches('/'), token),
+            chat_id,
+            interval,
+            last_sent: Mutex::new(None),
+            templates,
+        }))
+    }
+
+    fn buttons(event: &LiquidityEvent) -> Value {
+        let token = event.launched_token();
+        json!({
+            "inline_keyboard": [
+                [{ "text": "Chart", "url": event.chart }],
+                [
+                    { "text": format!("{} on explorer", token.symbol), "url": event.explorer_url("token", token.address) },
+                    { "text": "Pair", "url": event.explorer_url("address", event.pair) },
+                ],
+            ]
+        })
+    }
+
+    /// Posts the message, spaced from the previous one as Telegram asks.
+    async fn post(&self, payload: &Value) -> Result<()> {
+        let mut last_sent = self.last_sent.lock().await;
+        if let Some(last) = *last_sent {
+            tokio::time::sleep(self.interval.saturating_sub(last.elapsed())).await;
+        }
+        let response = self
+            .client
+            .post(format!("{}/sendMessage", self.endpoint))
+            .header("Content-Type", "application/json")
+            .body(payload.to_string())
+            .send()
+            .await;
+        *last_sent = Some(Instant::now());
+        check_response(response?).await
+    }
+}
+
+#[async_trait]
+impl AlertSink for TelegramSink {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    async fn send(&self, event: &LiquidityEvent) -> Result<()> {
+        let rendered = self
+            .templates
+            .as_ref()
+            .and_then(|templates| templates.render(Self::NAME, event, escape));
+        let text = match rendered {
+            Some(rendered) => format!("*{}*\n{}", escape(&rendered.title), rendered.body),
+            None => format_event(event),
+        };
+        let payload = json!({
+            "chat_id": self.chat_id,
+            "text": text,
+            "parse_mode": "MarkdownV2",
+            "disable_web_page_preview": true,
+            "reply_markup": Self::buttons(event),
+        });
+        self.post(&payload).await
+    }
+}
+
+/// The fields of the Discord embed as a MarkdownV2 message.
+fn format_event(event: &LiquidityEvent) -> String {
+    let mut lines = vec![format!("*{}*", escape(&event.title())), escape(&event.description())];
+    for (name, value) in event.fields() {
+        lines.push(field(&name, &value));
+    }
+    lines.push(format!("`{:#x}`", event.pair));
+    lines.join("\n")
+}
+
+fn field(name: &str, value: &str) -> String {
+    format!("*{}:* {}", escape(name), escape(value))
+}
+
+/// Escapes the characters reserved by MarkdownV2 outside of entities.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "_*[]()~`>#+-=|{}.!\\".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    use super::*;
+    use crate::alert::{rug_event, SendFailure};
+
+    /// Bot API answering each request with the next of `responses` (status and body),
+    /// the JSON bodies of the requests are sent back on the channel.
+    fn mock_api(responses: Vec<(&'static str, &'static str)>) -> (String, mpsc::Receiver<(String, Value)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (requests, received) = mpsc::channel();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut payload = vec![0; length];
+                reader.read_exact(&mut payload).unwrap();
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                requests.send((path, serde_json::from_slice(&payload).unwrap())).unwrap();
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn sink(api_url: &str) -> TelegramSink {
+        TelegramSink {
+            client: reqwest::Client::new(),
+            endpoint: format!("{}/bot123:abc", api_url),
+            chat_id: "42".to_string(),
+            interval: CHAT_INTERVAL,
+            last_sent: Mutex::new(None),
+            templates: None,
+        }
+    }
+
+    #[test]
+    fn escapes_markdown_v2() {
+        assert_eq!(escape("a_b*c [d](e) 1.5 | x-y!"), "a\\_b\\*c \\[d\\]\\(e\\) 1\\.5 \\| x\\-y\\!");
+        assert_eq!(escape("plain text"), "plain text");
+    }
+
+    #[tokio::test]
+    async fn sends_to_the_bot_api() {
+        let (url, requests) = mock_api(vec![
+            ("429 Too Many Requests", r#"{"ok":false,"error_code":429,"parameters":{"retry_after":7}}"#),
+            ("200 OK", r#"{"ok":true}"#),
+        ]);
+        let sink = sink(&url);
+        let event = rug_event();
+
+        let err = sink.send(&event).await.unwrap_err();
+        match err.downcast_ref::<SendFailure>() {
+            Some(SendFailure::RetryAfter(wait)) => assert_eq!(*wait, Duration::from_secs(7)),
+            _ => panic!("expected a retry after, got {}", err),
+        }
+        sink.send(&event).await.unwrap();
+
+        for _ in 0..2 {
+            let (path, payload) = requests.recv().unwrap();
+            assert_eq!(path, "/bot123:abc/sendMessage");
+            assert_eq!(payload["chat_id"], "42");
+            assert_eq!(payload["parse_mode"], "MarkdownV2");
+            let text = payload["text"].as_str().unwrap();
+            assert!(text.starts_with("*Uniswap V2 \\- Liquidity pulled*\n"), "{}", text);
+            assert!(text.contains("50\\.0% of the liquidity removed from pair TKN/WETH 12 minutes after launch"), "{}", text);
+            assert!(text.contains("*Removed:* 1\\.5 TKN \\| 2 WETH"), "{}", text);
+            assert_eq!(
+                payload["reply_markup"],
+                json!({
+                    "inline_keyboard": [
+                        [{ "text": "Chart", "url": "https://dexscreener.com/ethereum/0x0000000000000000000000000000000000000011" }],
+                        [
+                            { "text": "TKN on explorer", "url": "https://etherscan.io/token/0x00000000000000000000000000000000000000aa" },
+                            { "text": "Pair", "url": "https://etherscan.io/address/0x0000000000000000000000000000000000000011" },
+                        ],
+                    ]
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,497 @@
+//! Messages of the chat sinks written by the user, loaded from a JSON file:
+//!
+//! ```json
+//! {
+//!     "telegram": {
+//!         "liquidity": {
+//!             "title": "{{token.symbol}} launched on {{dex}}",
+//!             "body": "{{token.name}} \\| ${{value_usd | round:0 | default:?}}\n{{#each fields}}{{this.name}}: {{this.value}}\n{{/each}}"
+//!         }
+//!     },
+//!     "default": {
+//!         "rug": { "body": "{{pulled | percent}} pulled from {{token.symbol}} after {{minutes}} minutes" }
+//!     }
+//! }
+//! ```
+//!
+//! Messages are picked by sink then kind of event, `default` standing for any of
+//! them. A template sees the JSON event of the webhook sink, plus a few values
+//! computed for the chats (`title`, `description`, `fields`, `token`, the explorer
+//! links...). The title is plain text, the body is in the markup of the sink and
+//! the values inserted in it are escaped.
+
+use std::collections::HashMap;
+use std::fs;
+
+use eyre::{eyre, Result, WrapErr};
+use serde::Deserialize;
+use serde_json::{json, Value as Json};
+
+use super::{LiquidityEvent, SINKS};
+use super::webhook::WebhookSink;
+use crate::token::Token;
+
+/// Sink or kind of event a message applies to when nothing more specific is set.
+const DEFAULT: &str = "default";
+const KINDS: [&str; 5] = ["liquidity", "position", "rug", "pending", "retracted"];
+
+/// Top level values of the template context, see `context`.
+const CONTEXT: [&str; 41] = [
+    "kind", "chain", "dex", "website", "logo", "pair", "chart", "token0", "token1", "listing", "launched",
+    "amount0", "amount1", "reserves", "value_usd", "tx_hash", "block", "deployer", "lp", "rule",
+    "fee", "price", "owner", "tick_lower", "tick_upper", "price_lower", "price_upper", "pulled", "minutes",
+    "sender", "gas_price", "deadline", "new_pair", "event",
+    "title", "description", "fields", "token", "token_url", "pair_url", "tx_url",
+];
+
+/// Templates of every sink, by sink and kind of event.
+#[derive(Debug, Default)]
+pub struct Templates {
+    messages: HashMap<String, HashMap<String, Message>>,
+}
+
+#[derive(Debug)]
+struct Message {
+    /// The title of the event when `None`.
+    title: Option<Template>,
+    body: Template,
+}
+
+/// A message rendered for an event.
+pub struct Rendered {
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMessage {
+    title: Option<String>,
+    body: String,
+}
+
+impl Templates {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).wrap_err_with(|| format!("Can't read templates {}", path))?;
+        let raw: HashMap<String, HashMap<String, RawMessage>> =
+            serde_json::from_str(&content).wrap_err_with(|| format!("Invalid templates file {}", path))?;
+        let mut messages = HashMap::new();
+        for (sink, kinds) in raw {
+            if sink == WebhookSink::NAME {
+                return Err(eyre!("{}: the webhook sink sends the JSON event, it has no template", sink));
+            }
+            if sink != DEFAULT && !SINKS.contains(&sink.as_str()) {
+                return Err(eyre!("unknown sink \"{}\", expected default or one of {}", sink, SINKS.join(", ")));
+            }
+            let mut parsed = HashMap::new();
+            for (kind, message) in kinds {
+                if kind != DEFAULT && !KINDS.contains(&kind.as_str()) {
+                    return Err(eyre!(
+                        "{}: unknown event \"{}\", expected default or one of {}",
+                        sink,
+                        kind,
+                        KINDS.join(", ")
+                    ));
+                }
+                let path = format!("{}.{}", sink, kind);
+                let title = match message.title {
+                    Some(title) => Some(Template::parse(&title).wrap_err_with(|| format!("{}.title", path))?),
+                    None => None,
+                };
+                let body = Template::parse(&message.body).wrap_err_with(|| format!("{}.body", path))?;
+                parsed.insert(kind, Message { title, body });
+            }
+            messages.insert(sink, parsed);
+        }
+        Ok(Self { messages })
+    }
+
+    /// Number of messages defined.
+    pub fn len(&self) -> usize {
+        self.messages.values().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Most specific message of the sink for the kind of event: the sink's own for the
+    /// kind, then for any kind, then the default ones.
+    fn message(&self, sink: &str, kind: &str) -> Option<&Message> {
+        [sink, DEFAULT]
+            .iter()
+            .filter_map(|sink| self.messages.get(*sink))
+            .find_map(|kinds| kinds.get(kind).or_else(|| kinds.get(DEFAULT)))
+    }
+
+    /// The event as the sink's message, `None` when the sink keeps its own format.
+    /// `escape` is applied to the values inserted in the body.
+    pub fn render(&self, sink: &str, event: &LiquidityEvent, escape: fn(&str) -> String) -> Option<Rendered> {
+        let message = self.message(sink, event.kind.name())?;
+        let context = context(event);
+        let title = match &message.title {
+            Some(title) => title.render(&context, str::to_string),
+            None => event.title(),
+        };
+        Some(Rendered {
+            title,
+            body: message.body.render(&context, escape),
+        })
+    }
+}
+
+/// What a template can use: the JSON event, with the block as a number, and the
+/// values the chat sinks show by default.
+fn context(event: &LiquidityEvent) -> Json {
+    let mut context = serde_json::to_value(event).unwrap_or_default();
+    let token = event.launched_token();
+    let extra = json!({
+        "block": event.block.map(|block| block.as_u64()),
+        "title": event.title(),
+        "description": event.description(),
+        "fields": event
+            .fields()
+            .into_iter()
+            .map(|(name, value)| json!({ "name": name, "value": value }))
+            .collect::<Vec<Json>>(),
+        "token": token,
+        "token_url": event.explorer_url("token", token.address),
+        "pair_url": event.explorer_url("address", event.pair),
+        "tx_url": event
+            .tx_hash
+            .map(|hash| format!("https://{}tx/{:#x}", Token::get_explorer(event.chain.clone()), hash)),
+    });
+    if let (Json::Object(context), Json::Object(extra)) = (&mut context, extra) {
+        context.extend(extra);
+    }
+    context
+}
+
+/// A parsed template: `{{path}}` inserts a value, `{{path | filter}}` transforms it
+/// first, `{{#if path}}..{{else}}..{{/if}}` and `{{#each path}}..{{/each}}` are
+/// blocks, `this` being the current item of an `each`.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Value(Path, Vec<Filter>),
+    If(Path, Vec<Node>, Vec<Node>),
+    Each(Path, Vec<Node>),
+}
+
+/// Dotted path to a value, array items being indexed by number (`reserves.0`).
+#[derive(Debug)]
+struct Path(Vec<String>);
+
+#[derive(Debug)]
+enum Filter {
+    /// Number with that many decimals.
+    Round(usize),
+    /// Share from 0 to 1 as a percentage.
+    Percent,
+    Upper,
+    Lower,
+    /// Address or hash shortened to its start and end.
+    Short,
+    /// Text used when the value is missing.
+    Default(String),
+}
+
+enum Piece {
+    Text(String),
+    Tag(String),
+}
+
+/// Where the paths are resolved.
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    root: &'a Json,
+    this: Option<&'a Json>,
+}
+
+static NULL: Json = Json::Null;
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut pieces = tokenize(source)?.into_iter();
+        match parse_nodes(&mut pieces, false)? {
+            (nodes, None) => Ok(Self { nodes }),
+            (_, Some(tag)) => Err(eyre!("unexpected {{{{{}}}}}", tag)),
+        }
+    }
+
+    pub fn render(&self, context: &Json, escape: fn(&str) -> String) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, Scope { root: context, this: None }, escape, &mut out);
+        out
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Piece>> {
+    let mut pieces = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            pieces.push(Piece::Text(rest[..start].to_string()));
+        }
+        let tag = &rest[start + 2..];
+        let end = tag
+            .find("}}")
+            .ok_or_else(|| eyre!("unclosed {{{{ before \"{}\"", tag.chars().take(20).collect::<String>()))?;
+        pieces.push(Piece::Tag(tag[..end].trim().to_string()));
+        rest = &tag[end + 2..];
+    }
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest.to_string()));
+    }
+    Ok(pieces)
+}
+
+/// Parses until the end of the template or a closing tag (`else`, `/if`, `/each`),
+/// which is returned for the enclosing block to check.
+fn parse_nodes(pieces: &mut impl Iterator<Item = Piece>, in_each: bool) -> Result<(Vec<Node>, Option<String>)> {
+    let mut nodes = Vec::new();
+    while let Some(piece) = pieces.next() {
+        let tag = match piece {
+            Piece::Text(text) => {
+                nodes.push(Node::Text(text));
+                continue;
+            }
+            Piece::Tag(tag) => tag,
+        };
+        if tag == "else" || tag.starts_with('/') {
+            return Ok((nodes, Some(tag)));
+        }
+        if let Some(path) = tag.strip_prefix("#if ") {
+            let path = Path::parse(path, in_each)?;
+            let (then, end) = parse_nodes(pieces, in_each)?;
+            let (otherwise, end) = match end.as_deref() {
+                Some("else") => parse_nodes(pieces, in_each)?,
+                _ => (Vec::new(), end),
+            };
+            expect_end(end, "/if")?;
+            nodes.push(Node::If(path, then, otherwise));
+        } else if let Some(path) = tag.strip_prefix("#each ") {
+            let path = Path::parse(path, in_each)?;
+            let (body, end) = parse_nodes(pieces, true)?;
+            expect_end(end, "/each")?;
+            nodes.push(Node::Each(path, body));
+        } else if tag.starts_with('#') {
+            return Err(eyre!("unknown block {{{{{}}}}}, expected #if or #each", tag));
+        } else {
+            let mut parts = tag.split('|');
+            let path = Path::parse(parts.next().unwrap_or_default(), in_each)?;
+            let filters = parts.map(Filter::parse).collect::<Result<Vec<Filter>>>()?;
+            nodes.push(Node::Value(path, filters));
+        }
+    }
+    Ok((nodes, None))
+}
+
+fn expect_end(end: Option<String>, expected: &str) -> Result<()> {
+    match end {
+        Some(tag) if tag == expected => Ok(()),
+        Some(tag) => Err(eyre!("expected {{{{{}}}}}, found {{{{{}}}}}", expected, tag)),
+        None => Err(eyre!("missing {{{{{}}}}}", expected)),
+    }
+}
+
+fn render_nodes(nodes: &[Node], scope: Scope, escape: fn(&str) -> String, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value(path, filters) => {
+                let value = filters.iter().fold(path.resolve(scope).clone(), |value, filter| filter.apply(value));
+                out.push_str(&escape(&format_value(&value)));
+            }
+            Node::If(path, then, otherwise) => {
+                let branch = if is_truthy(path.resolve(scope)) { then } else { otherwise };
+                render_nodes(branch, scope, escape, out);
+            }
+            Node::Each(path, body) => {
+                if let Json::Array(items) = path.resolve(scope) {
+                    for item in items {
+                        render_nodes(body, Scope { this: Some(item), ..scope }, escape, out);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Path {
+    fn parse(source: &str, in_each: bool) -> Result<Self> {
+        let source = source.trim();
+        let segments: Vec<String> = source.split('.').map(str::to_string).collect();
+        let valid = |segment: &String| !segment.is_empty() && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !segments.iter().all(valid) {
+            return Err(eyre!("invalid path \"{}\"", source));
+        }
+        match segments[0].as_str() {
+            "this" if in_each => {}
+            "this" => return Err(eyre!("\"{}\": this is only set inside {{{{#each}}}}", source)),
+            root if !CONTEXT.contains(&root) => {
+                return Err(eyre!("unknown value \"{}\", expected one of {}", root, CONTEXT.join(", ")));
+            }
+            _ => {}
+        }
+        Ok(Self(segments))
+    }
+
+    fn resolve<'a>(&self, scope: Scope<'a>) -> &'a Json {
+        let (mut value, segments) = match self.0.split_first() {
+            Some((first, rest)) if first == "this" => (scope.this.unwrap_or(&NULL), rest),
+            _ => (scope.root, &self.0[..]),
+        };
+        for segment in segments {
+            value = match value {
+                Json::Object(map) => map.get(segment),
+                Json::Array(items) => segment.parse::<usize>().ok().and_then(|index| items.get(index)),
+                _ => None,
+            }
+            .unwrap_or(&NULL);
+        }
+        value
+    }
+}
+
+impl Filter {
+    fn parse(source: &str) -> Result<Self> {
+        let source = source.trim();
+        let (name, arg) = match source.split_once(':') {
+            Some((name, arg)) => (name.trim(), Some(arg.trim())),
+            None => (source, None),
+        };
+        match (name, arg) {
+            ("round", Some(decimals)) => decimals
+                .parse()
+                .map(Filter::Round)
+                .map_err(|_| eyre!("round:{} expects a number of decimals", decimals)),
+            ("percent", None) => Ok(Filter::Percent),
+            ("upper", None) => Ok(Filter::Upper),
+            ("lower", None) => Ok(Filter::Lower),
+            ("short", None) => Ok(Filter::Short),
+            ("default", Some(text)) => Ok(Filter::Default(text.to_string())),
+            _ => Err(eyre!(
+                "unknown filter \"{}\", expected round:N, percent, upper, lower, short or default:TEXT",
+                source
+            )),
+        }
+    }
+
+    fn apply(&self, value: Json) -> Json {
+        match (self, &value) {
+            (Filter::Round(decimals), Json::Number(number)) => {
+                Json::String(format!("{:.*}", decimals, number.as_f64().unwrap_or_default()))
+            }
+            (Filter::Percent, Json::Number(number)) => {
+                Json::String(format!("{:.1}%", number.as_f64().unwrap_or_default() * 100.0))
+            }
+            (Filter::Upper, Json::String(text)) => Json::String(text.to_uppercase()),
+            (Filter::Lower, Json::String(text)) => Json::String(text.to_lowercase()),
+            (Filter::Short, Json::String(text)) if text.len() > 12 && text.is_ascii() => {
+                Json::String(format!("{}…{}", &text[..6], &text[text.len() - 4..]))
+            }
+            (Filter::Default(text), _) if !is_truthy(&value) && !matches!(value, Json::Bool(_) | Json::Number(_)) => {
+                Json::String(text.clone())
+            }
+            _ => value,
+        }
+    }
+}
+
+fn is_truthy(value: &Json) -> bool {
+    match value {
+        Json::Null => false,
+        Json::Bool(value) => *value,
+        Json::Number(number) => number.as_f64() != Some(0.0),
+        Json::String(text) => !text.is_empty(),
+        Json::Array(items) => !items.is_empty(),
+        Json::Object(_) => true,
+    }
+}
+
+fn format_value(value: &Json) -> String {
+    match value {
+        Json::Null => String::new(),
+        Json::Bool(value) => if *value { "yes" } else { "no" }.to_string(),
+        Json::String(text) => text.clone(),
+        Json::Number(number) => number.to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(source: &str) -> String {
+        Template::parse(source).unwrap_err().to_string()
+    }
+
+    fn brackets(text: &str) -> String {
+        format!("[{}]", text)
+    }
+
+    #[test]
+    fn rejects_unclosed_tag() {
+        assert!(parse_error("{{token.symbol").starts_with("unclosed {{"));
+    }
+
+    #[test]
+    fn rejects_mismatched_blocks() {
+        assert_eq!(parse_error("{{#if lp}}locked{{/each}}"), "expected {{/if}}, found {{/each}}");
+        assert_eq!(parse_error("{{#each fields}}{{this.name}}{{/if}}"), "expected {{/each}}, found {{/if}}");
+        assert_eq!(parse_error("{{#if lp}}locked"), "missing {{/if}}");
+        assert_eq!(parse_error("{{#each fields}}{{this.name}}"), "missing {{/each}}");
+        assert_eq!(parse_error("{{token.symbol}}{{/if}}"), "unexpected {{/if}}");
+        assert!(parse_error("{{#with token}}{{/with}}").starts_with("unknown block {{#with token}}"));
+    }
+
+    #[test]
+    fn rejects_unknown_roots() {
+        assert!(parse_error("{{symbol}}").starts_with("unknown value \"symbol\""));
+        assert!(parse_error("{{this.name}}").contains("this is only set inside"));
+        assert_eq!(parse_error("{{token..symbol}}"), "invalid path \"token..symbol\"");
+    }
+
+    #[test]
+    fn rejects_unknown_filters() {
+        assert!(parse_error("{{pulled | half}}").starts_with("unknown filter \"half\""));
+        assert!(parse_error("{{value_usd | round}}").starts_with("unknown filter \"round\""));
+        assert_eq!(parse_error("{{value_usd | round:two}}"), "round:two expects a number of decimals");
+    }
+
+    #[test]
+    fn renders_with_escaping() {
+        let context = json!({
+            "token": { "symbol": "a*b" },
+            "value_usd": 1234.567,
+            "pulled": 0.5,
+            "lp": null,
+            "fields": [{ "name": "Tax", "value": "5%" }, { "name": "Owner", "value": "x_y" }],
+        });
+        let template = Template::parse(
+            "*{{token.symbol | upper}}* ${{value_usd | round:1}} {{pulled | percent}} \
+             {{#if lp}}locked{{else}}{{lp | default:none}}{{/if}}\
+             {{#each fields}} {{this.name}}={{this.value}}{{/each}}",
+        )
+        .unwrap();
+        assert_eq!(
+            template.render(&context, brackets),
+            "*[A*B]* $[1234.6] [50.0%] [none] [Tax]=[5%] [Owner]=[x_y]"
+        );
+        assert_eq!(template.render(&context, str::to_string), "*A*B* $1234.6 50.0% none Tax=5% Owner=x_y");
+    }
+
+    #[test]
+    fn renders_missing_values_empty() {
+        let template = Template::parse("{{token.name}}|{{reserves.1}}|{{new_pair}}").unwrap();
+        let context = json!({ "reserves": [1.5, 2.5], "new_pair": true });
+        assert_eq!(template.render(&context, str::to_string), "|2.5|yes");
+    }
+}
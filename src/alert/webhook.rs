@@ -0,0 +1,116 @@
+use async_trait::async_trait;
+use eyre::{eyre, Result};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+use super::{check_response, AlertSink, LiquidityEvent, SCHEMA_VERSION};
+
+/// Header carrying `sha256=<hex HMAC of the body>` when a secret is set.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// Body of the webhook: the event with the version of its schema.
+#[derive(Serialize)]
+struct Payload<'a> {
+    version: u32,
+    #[serde(flatten)]
+    event: &'a LiquidityEvent,
+}
+
+/// Generic HTTP webhook, the JSON of each event is POSTed to `WEBHOOK_URL_{CHAIN}`
+/// and signed with `WEBHOOK_SECRET_{CHAIN}` when set.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+}
+
+impl WebhookSink {
+    pub const NAME: &'static str = "webhook";
+
+    /// `None` when the chain has no webhook URL.
+    pub fn from_env(chain: &str, client: reqwest::Client) -> Result<Option<Self>> {
+        let chain_str = chain.to_uppercase();
+        let url = match std::env::var(format!("WEBHOOK_URL_{}", chain_str)) {
+            Ok(url) if !url.is_empty() => url,
+            _ => return Ok(None),
+        };
+        reqwest::Url::parse(&url).map_err(|err| eyre!("Invalid WEBHOOK_URL_{}: {}", chain_str, err))?;
+        let secret = std::env::var(format!("WEBHOOK_SECRET_{}", chain_str))
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        Ok(Some(Self { client, url, secret }))
+    }
+
+    fn signature(&self, body: &str) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).ok()?;
+        mac.update(body.as_bytes());
+        Some(format!("sha256={}", hex::encode(mac.finalize().into_bytes())))
+    }
+}
+
+#[async_trait]
+impl AlertSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    async fn send(&self, event: &LiquidityEvent) -> Result<()> {
+        let body = serde_json::to_string(&Payload {
+            version: SCHEMA_VERSION,
+            event,
+        })?;
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json");
+        if let Some(signature) = self.signature(&body) {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+        check_response(request.body(body).send().await?).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::alert::rug_event;
+
+    fn sink(secret: Option<&str>) -> WebhookSink {
+        WebhookSink {
+            client: reqwest::Client::new(),
+            url: "http://127.0.0.1:1/hook".to_string(),
+            secret: secret.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn signs_the_body() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sink(Some("Jefe")).signature("what do ya want for nothing?").as_deref(),
+            Some("sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843")
+        );
+        assert_eq!(sink(None).signature("what do ya want for nothing?"), None);
+    }
+
+    #[test]
+    fn flattens_the_version_next_to_the_event() {
+        let event = rug_event();
+        let body: Value = serde_json::to_value(Payload {
+            version: SCHEMA_VERSION,
+            event: &event,
+        })
+        .unwrap();
+
+        assert_eq!(body["version"], json!(SCHEMA_VERSION));
+        assert_eq!(body["kind"], "rug");
+        assert_eq!(body["pulled"], 0.5);
+        assert_eq!(body["pair"], "0x0000000000000000000000000000000000000011");
+        assert_eq!(body["token0"]["symbol"], "TKN");
+        assert!(body.get("event").is_none(), "{}", body);
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use eyre::Result;
+
+use crate::timestamp_print;
+use colored::*;
+
+/// Blocks requested per `eth_getLogs` call at first.
+const INITIAL_PAGE: u64 = 5_000;
+const MAX_PAGE: u64 = 100_000;
+
+/// Pages `eth_getLogs` over a block range.
+///
+/// The page is halved whenever the node refuses the request because of the
+/// number of results or the range size, and grows back after each success.
+pub struct LogPager {
+    http: Arc<Provider<Http>>,
+    filter: Filter,
+    next: u64,
+    to: u64,
+    page: u64,
+}
+
+impl LogPager {
+    pub fn new(http: Arc<Provider<Http>>, filter: Filter, from_block: u64, to_block: u64) -> Self {
+        Self {
+            http,
+            filter,
+            next: from_block,
+            to: to_block,
+            page: INITIAL_PAGE,
+        }
+    }
+
+    /// Logs of the next page, `None` once the whole range was fetched.
+    pub async fn next_page(&mut self) -> Result<Option<Vec<Log>>> {
+        while self.next <= self.to {
+            let end = (self.next + self.page - 1).min(self.to);
+            let filter = self.filter.clone().from_block(self.next).to_block(end);
+            match self.http.get_logs(&filter).await {
+                Ok(logs) => {
+                    self.next = end + 1;
+                    self.page = (self.page * 2).min(MAX_PAGE);
+                    return Ok(Some(logs));
+                }
+                Err(err) if self.page > 1 && is_range_error(&err.to_string()) => {
+                    self.page /= 2;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Last block fetched so far.
+    pub fn progress(&self) -> u64 {
+        self.next.saturating_sub(1)
+    }
+}
+
+/// Errors returned by the nodes when a `eth_getLogs` range has to be reduced.
+fn is_range_error(err: &str) -> bool {
+    let err = err.to_lowercase();
+    [
+        "too many",
+        "more than",
+        "limit exceeded",
+        "range",
+        "response size",
+        "timeout",
+        "timed out",
+    ]
+    .iter()
+    .any(|pattern| err.contains(pattern))
+}
+
+/// Prints how far a backfill went.
+pub fn print_progress(chain: &str, name: &str, pager: &LogPager, to_block: u64, found: usize) {
+    timestamp_print!(
+        Color::White,
+        Some(false),
+        chain,
+        format!(
+            "[BACKFILL][{}] Block {}/{} | {} pairs found",
+            name,
+            pager.progress(),
+            to_block,
+            found
+        )
+    );
+}
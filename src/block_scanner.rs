@@ -1,25 +1,233 @@
-use std::{sync::Arc, time::Duration};
+//! New blocks of a chain, pushed by a `newHeads` WebSocket subscription and
+//! broadcast to whatever follows the head (log confirmations, reorgs...) and
+//! published on the event bus.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use ethers::prelude::*;
-use tokio::time::sleep;
+use eyre::{eyre, Result};
+use tokio::sync::broadcast;
+use tokio::time::{timeout, Instant};
 
+use crate::events::{EventBus, TrackerEvent};
 use crate::timestamp_print;
 use colored::*;
 
-pub async fn loop_blocks(http_provider: Arc<Provider<Http>>) {
-    let mut last_block: U64 = U64::zero();
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Headers kept for a receiver that falls behind.
+const CAPACITY: usize = 256;
+/// Largest gap between two heads filled by fetching the blocks in between.
+const MAX_GAP: u64 = 128;
+/// The subscription is considered dead after that many block times without a head.
+const STALL_BLOCKS: u32 = 10;
+const MIN_STALL: Duration = Duration::from_secs(60);
+
+/// Average time between two blocks of the chain, also the HTTP polling period.
+pub fn block_time(chain: &str) -> Duration {
+    match chain {
+        "ETHEREUM" => Duration::from_secs(12),
+        "ARBITRUM" => Duration::from_millis(250),
+        "POLYGON" => Duration::from_secs(2),
+        "BSC" => Duration::from_secs(3),
+        "FANTOM" => Duration::from_secs(1),
+        "AVALANCHE" => Duration::from_secs(2),
+        "CRONOS" => Duration::from_secs(6),
+        _ => Duration::from_secs(12),
+    }
+}
+
+/// The part of a block the subscribers need.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub number: U64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    pub timestamp: U256,
+    pub base_fee: Option<U256>,
+}
+
+impl BlockHeader {
+    /// `None` for a pending block, which has no number or hash yet.
+    fn from_block<T>(block: &Block<T>) -> Option<Self> {
+        Some(Self {
+            number: block.number?,
+            hash: block.hash?,
+            parent_hash: block.parent_hash,
+            timestamp: block.timestamp,
+            base_fee: block.base_fee_per_gas,
+        })
+    }
+}
+
+/// Head of a chain. The `newHeads` subscription is supervised: when it fails or
+/// stalls, the head is polled over HTTP every block time while it reconnects with
+/// an exponential backoff. Missed blocks are fetched so subscribers see every
+/// block in order, a reorg shows up as a header whose parent isn't the previous one.
+///
+/// Nothing is connected until the first subscriber or `start`, a backfill never
+/// follows the head.
+#[derive(Clone, Debug)]
+pub struct BlockTracker {
+    headers: broadcast::Sender<BlockHeader>,
+    /// The head to follow and its WebSocket URL, until the tracking starts.
+    idle: Arc<Mutex<Option<(Head, String)>>>,
+}
+
+impl BlockTracker {
+    pub fn new(chain: String, ws_url: String, http: Arc<Provider<Http>>, events: EventBus) -> Self {
+        let (headers, _) = broadcast::channel(CAPACITY);
+        let head = Head {
+            chain,
+            http,
+            headers: headers.clone(),
+            events,
+            last: None,
+        };
+        Self {
+            headers,
+            idle: Arc::new(Mutex::new(Some((head, ws_url)))),
+        }
+    }
+
+    /// Headers of the blocks from now on, the first call starts the tracking.
+    pub fn subscribe(&self) -> broadcast::Receiver<BlockHeader> {
+        let headers = self.headers.subscribe();
+        self.start();
+        headers
+    }
+
+    /// Starts following the head if it isn't already, the blocks are then published
+    /// on the event bus even without a header subscriber.
+    pub fn start(&self) {
+        if let Some((head, ws_url)) = self.idle.lock().unwrap().take() {
+            tokio::spawn(track(head, ws_url));
+        }
+    }
+}
+
+/// Last header broadcast, whatever the source.
+#[derive(Debug)]
+struct Head {
+    chain: String,
+    http: Arc<Provider<Http>>,
+    headers: broadcast::Sender<BlockHeader>,
+    events: EventBus,
+    last: Option<BlockHeader>,
+}
+
+impl Head {
+    /// Broadcasts a new head, after the blocks missed since the last one.
+    async fn push(&mut self, header: BlockHeader) {
+        if let Some(last) = &self.last {
+            if header.hash == last.hash {
+                return;
+            }
+            let (from, to) = (last.number.as_u64() + 1, header.number.as_u64());
+            if to > from && to - from <= MAX_GAP {
+                for number in from..to {
+                    match self.http.get_block(number).await {
+                        Ok(Some(block)) => {
+                            if let Some(missed) = BlockHeader::from_block(&block) {
+                                self.send(missed);
+                            }
+                        }
+                        _ => break,
+                    }
+                }
+            }
+        }
+        self.send(header.clone());
+        self.last = Some(header);
+    }
+
+    fn send(&self, header: BlockHeader) {
+        self.events.publish(TrackerEvent::block_seen(&self.chain, &header));
+        // No receiver is not an error, the head is still followed.
+        let _ = self.headers.send(header);
+    }
+
+    /// Polls the head over HTTP every block time, for `duration`.
+    async fn poll(&mut self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        let mut interval = tokio::time::interval(block_time(&self.chain));
+        while Instant::now() < deadline {
+            interval.tick().await;
+            if let Ok(Some(block)) = self.http.get_block(BlockNumber::Latest).await {
+                let header = match BlockHeader::from_block(&block) {
+                    Some(header) => header,
+                    None => continue,
+                };
+                // A node behind the load balancer may still be on an older block.
+                match &self.last {
+                    Some(last) if header.number < last.number => {}
+                    _ => self.push(header).await,
+                }
+            }
+        }
+    }
+
+    /// Follows `newHeads` until the subscription fails or stalls.
+    async fn follow(&mut self, ws_url: &str, backoff: &mut Duration) -> Result<()> {
+        let ws = Provider::<Ws>::connect(ws_url).await?;
+        let mut stream = ws.subscribe_blocks().await?;
+        let stall = (block_time(&self.chain) * STALL_BLOCKS).max(MIN_STALL);
+        loop {
+            match timeout(stall, stream.next()).await {
+                Ok(Some(block)) => {
+                    *backoff = MIN_BACKOFF;
+                    if let Some(header) = BlockHeader::from_block(&block) {
+                        self.push(header).await;
+                    }
+                }
+                Ok(None) => return Err(eyre!("subscription ended")),
+                Err(_) => return Err(eyre!("no new block for {}s", stall.as_secs())),
+            }
+        }
+    }
+}
 
+async fn track(mut head: Head, ws_url: String) {
+    let mut backoff = MIN_BACKOFF;
     loop {
-        if let Ok(block) = http_provider.get_block_number().await {
-            if block > last_block {
-                last_block = block;
+        if let Err(err) = head.follow(&ws_url, &mut backoff).await {
+            timestamp_print!(
+                Color::Red,
+                Some(false),
+                head.chain,
+                format!(
+                    "[BLOCK] newHeads lost: {}, polling over HTTP every {}ms and reconnecting in {}s",
+                    err,
+                    block_time(&head.chain).as_millis(),
+                    backoff.as_secs()
+                )
+            );
+        }
+        head.poll(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Prints a banner for every new block of `chain`.
+pub async fn loop_blocks(mut events: broadcast::Receiver<TrackerEvent>, chain: String) {
+    loop {
+        match events.recv().await {
+            Ok(TrackerEvent::BlockSeen { chain: block_chain, number, .. }) if block_chain == chain => {
                 timestamp_print!(
                     Color::White,
                     Some(false),
-                    format!("---------- BLOCK: {:?} ----------", block)
+                    chain,
+                    format!("---------- BLOCK: {:?} ----------", number)
                 );
             }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                timestamp_print!(Color::Yellow, Some(false), chain, format!("[BLOCK] {} events skipped", skipped));
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
         }
-        sleep(Duration::from_millis(1)).await;
     }
 }
@@ -1,10 +1,58 @@
 use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
+use ethers::types::{Address, H256};
+use eyre::{eyre, Result, WrapErr};
+
+/// How a dex factory and its pools have to be handled.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DexKind {
+    /// `PairCreated(token0, token1, pair, uint)` + V2 `Mint`.
+    #[default]
+    UniV2,
+    /// `PoolCreated(token0, token1, fee, tickSpacing, pool)` + V3 `Mint`.
+    UniV3,
+    /// `PairCreated(token0, token1, stable, pair, uint)` + V2 `Mint`.
+    Solidly,
+}
+
+impl std::fmt::Display for DexKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DexKind::UniV2 => write!(f, "univ2"),
+            DexKind::UniV3 => write!(f, "univ3"),
+            DexKind::Solidly => write!(f, "solidly"),
+        }
+    }
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct ContractAddresses {
-    pub router: String,
-    pub factory: String,
+    pub router: Address,
+    pub factory: Address,
+    #[serde(default)]
+    pub kind: DexKind,
+    /// Pair init code hash, used to compute pair addresses offline.
+    #[serde(default)]
+    pub init_code_hash: Option<H256>,
+    /// Swap fee in basis points.
+    #[serde(default)]
+    pub fee: Option<u32>,
+    /// Block the factory was deployed at.
+    #[serde(default)]
+    pub start_block: Option<u64>,
+    /// Display name, defaults to the built-in one for known factories, then to the entry key.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Dex website, defaults to the built-in one for known factories.
+    #[serde(default)]
+    pub website: Option<String>,
+    /// Logo URL shown in the alerts.
+    #[serde(default)]
+    pub logo: Option<String>,
+    /// Chain slug of the chart links (dexscreener.com/<slug>/<pair>), defaults to the chain name.
+    #[serde(default)]
+    pub chart_slug: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -13,10 +61,67 @@ pub struct ChainData {
    pub addresses: HashMap<String, ContractAddresses>,
 }
 
-pub fn get_contracts_data(path: String) -> HashMap<String, ChainData> {
-    let path = std::path::Path::new(path.as_str());
-    let content = std::fs::read_to_string(path).expect("Can't read file");
-    let data: HashMap<String, ChainData> =
-        serde_json::from_str(&content).expect("JSON deserialization failed");
-    data
-}
\ No newline at end of file
+/// Loads the dex contracts file, every entry is validated so a typo in a `kind`
+/// or an address is reported with the chain and the dex it belongs to.
+pub fn get_contracts_data(path: String) -> Result<HashMap<String, ChainData>> {
+    let content = std::fs::read_to_string(std::path::Path::new(path.as_str()))
+        .wrap_err_with(|| format!("Can't read file {}", path))?;
+    let raw: HashMap<String, HashMap<String, serde_json::Value>> =
+        serde_json::from_str(&content).wrap_err("JSON deserialization failed")?;
+
+    let mut data = HashMap::new();
+    for (chain, dexes) in raw {
+        let mut addresses = HashMap::new();
+        for (dex, value) in dexes {
+            let contracts: ContractAddresses = serde_json::from_value(value)
+                .map_err(|e| eyre!("Invalid entry \"{}\" on chain {}: {}", dex, chain, e))?;
+            addresses.insert(dex, contracts);
+        }
+        data.insert(chain, ChainData { addresses });
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    /// Loads `content` through a contracts file named after the test.
+    fn load(name: &str, content: Value) -> Result<HashMap<String, ChainData>> {
+        let path = std::env::temp_dir().join(format!("dex_contracts_{}_{}.json", std::process::id(), name));
+        std::fs::write(&path, content.to_string()).unwrap();
+        let data = get_contracts_data(path.display().to_string());
+        let _ = std::fs::remove_file(&path);
+        data
+    }
+
+    fn entry(kind: Option<&str>) -> Value {
+        let mut entry = json!({
+            "router": "0x7a250d5630b4cf539739df2c5dacb4c659f2488d",
+            "factory": "0x5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f",
+        });
+        if let Some(kind) = kind {
+            entry["kind"] = json!(kind);
+        }
+        entry
+    }
+
+    #[test]
+    fn defaults_to_univ2() {
+        let data = load("default_kind", json!({ "ETHEREUM": { "Uniswap": entry(None), "UniswapV3": entry(Some("univ3")) } })).unwrap();
+        let addresses = &data["ETHEREUM"].addresses;
+        assert_eq!(addresses["Uniswap"].kind, DexKind::UniV2);
+        assert_eq!(addresses["UniswapV3"].kind, DexKind::UniV3);
+    }
+
+    #[test]
+    fn names_the_invalid_entry() {
+        let err = load("invalid_kind", json!({ "ETHEREUM": { "Uniswap": entry(Some("univ4")) } }))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.starts_with("Invalid entry \"Uniswap\" on chain ETHEREUM: unknown variant `univ4`"), "{}", err);
+    }
+}
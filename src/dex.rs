@@ -1,21 +1,37 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use ethers::prelude::*;
+use ethers::{contract::parse_log, prelude::*};
 
 use crate::{
-    address_book::{
-        UniV2Factory, UniV2Router, PANCAKESWAP_FACTORY, SUSHISWAPV2_FACTORY,
-        SUSHIV2_FACTORY_ARBITRUM, TRADERJOE_FACTORY_ARBITRUM, UNISWAPV2_FACTORY, UNISWAPV3_FACTORY,
-        VVS_FACTORY,
-    },
+    address_book::{known_dex, LpPair, UniV2Factory, UniV2Router},
     timestamp_print,
 };
 
-use crate::pair::Pair;
+use crate::backfill::{print_progress, LogPager};
+use crate::contracts::{ContractAddresses, DexKind};
+use crate::alert::{AlertDispatcher, LiquidityEvent, Route};
+use crate::alert::template::Templates;
+use crate::events::{EventBus, TokenMeta, TrackerEvent};
+use crate::filter::{Filters, Launch};
+use crate::helpers::{pair_address, u256_amount};
+use crate::mempool::{MempoolWatcher, PendingLiquidity};
+use crate::oracle::PriceOracle;
+use crate::quote::QuoteRegistry;
+use crate::pair::{BurnFilter, MintFilter, Pair, RugSettings};
+use crate::pool::{MintFilter as PoolMintFilter, Pool};
+use crate::store::{PairRecord, Store};
+use crate::subscription::LogSubscriber;
+use crate::token::Token;
+use crate::wallet::WalletProfile;
+use crate::Config;
 
 use colored::*;
 
 use eyre::Result;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 
 abigen!(
     UniV2Factory,
@@ -25,166 +41,894 @@ abigen!(
     ]"#
 );
 
+mod solidly {
+    use ethers::prelude::*;
+
+    abigen!(
+        SolidlyFactory,
+        r#"[
+            event PairCreated(address indexed token0, address indexed token1, bool stable, address pair, uint)
+        ]"#
+    );
+}
+
+use solidly::PairCreatedFilter as SolidlyPairCreatedFilter;
+
+/// How long a pair is remembered once its watch ended, to undo a late reorg of its logs.
+const KEEP_WATCHED: Duration = Duration::from_secs(15 * 60);
+
+/// A pair or pool decoded from a factory creation event.
+#[derive(Clone, Copy)]
+enum Created {
+    Pair {
+        address: Address,
+        token0: Address,
+        token1: Address,
+    },
+    Pool {
+        address: Address,
+        token0: Address,
+        token1: Address,
+        fee: u32,
+        tick_spacing: i32,
+    },
+}
+
+impl Created {
+    fn address(&self) -> Address {
+        match self {
+            Created::Pair { address, .. } | Created::Pool { address, .. } => *address,
+        }
+    }
+}
+
+/// A pair or pool whose logs are followed by a task.
+struct Watched {
+    created: Created,
+    block_number: U64,
+    task: JoinHandle<()>,
+    /// When the task was found finished.
+    ended: Option<Instant>,
+}
+
+/// Pairs and pools whose logs are followed on a chain. Kept by the chain rather than
+/// the dex, so a restarted dex stream neither watches a pair twice nor forgets what
+/// to undo on a reorg.
+#[derive(Default)]
+pub struct Watchers(Mutex<HashMap<Address, Watched>>);
+
+impl Watchers {
+    /// Remembers the task following a new pair, forgetting the ones that ended a while ago.
+    fn insert(&self, created: Created, block_number: U64, task: JoinHandle<()>) {
+        let mut watched = self.0.lock().unwrap();
+        watched.retain(|_, watch| {
+            if watch.ended.is_none() && watch.task.is_finished() {
+                watch.ended = Some(Instant::now());
+            }
+            match watch.ended {
+                Some(ended) => ended.elapsed() <= KEEP_WATCHED,
+                None => true,
+            }
+        });
+        let previous = watched.insert(
+            created.address(),
+            Watched {
+                created,
+                block_number,
+                task,
+                ended: None,
+            },
+        );
+        if let Some(previous) = previous {
+            previous.task.abort();
+        }
+    }
+
+    /// True while a task follows the pair.
+    fn is_watching(&self, address: Address) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .get(&address)
+            .is_some_and(|watch| !watch.task.is_finished())
+    }
+
+    fn get(&self, address: Address) -> Option<(Created, U64)> {
+        self.0.lock().unwrap().get(&address).map(|watch| (watch.created, watch.block_number))
+    }
+
+    /// Forgets a pair, its task is stopped.
+    fn remove(&self, address: Address) {
+        if let Some(watch) = self.0.lock().unwrap().remove(&address) {
+            watch.task.abort();
+        }
+    }
+}
+
+impl std::fmt::Debug for Watchers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Watchers").field(&self.0.lock().unwrap().len()).finish()
+    }
+}
+
+/// What the command line asks of every dex.
+#[derive(Clone)]
+pub struct Settings {
+    pub alert: bool,
+    pub verify: bool,
+    pub rug: RugSettings,
+    /// How long the LP token transfers of a new pair are followed, zero to disable.
+    pub lp_window: Duration,
+    /// Messages per minute sent to each alert sink.
+    pub alert_rate: u32,
+    /// Launches that are alerted, all of them when `None`.
+    pub filters: Option<Arc<Filters>>,
+    /// Messages of the chat sinks, their built-in format when `None`.
+    pub templates: Option<Arc<Templates>>,
+    /// Blocks mined on top of an event before it is processed, unless the chain sets its own.
+    pub confirmations: u64,
+    /// Watch the pending liquidity additions to the routers.
+    pub mempool: bool,
+    /// Where the tracker events are published.
+    pub events: EventBus,
+}
+
 #[allow(dead_code)]
 pub struct Dex {
     pub chain: String,
     pub name: String,
     pub factory_address: Address,
     pub router_address: Address,
+    pub kind: DexKind,
+    pub init_code_hash: Option<H256>,
+    /// Swap fee in basis points, when known.
+    pub fee: Option<u32>,
+    pub start_block: Option<u64>,
+    pub website: Option<String>,
+    pub logo: Option<String>,
+    pub chart_slug: String,
+    pub store: Option<Arc<Store>>,
+    pub oracle: Option<Arc<PriceOracle>>,
+    pub quotes: Arc<QuoteRegistry>,
+    pub alerts: Arc<AlertDispatcher>,
+    pub events: EventBus,
     factory: UniV2Factory<Provider<Http>>,
     router: UniV2Router<Provider<Http>>,
     pub alert: bool,
     pub verify: bool,
+    pub rug: RugSettings,
+    pub lp_window: Duration,
+    pub filters: Option<Arc<Filters>>,
+    /// Pairs and pools watched on the chain, to undo what was done for them on a reorg.
+    watched: Arc<Watchers>,
+    /// Pending transactions of the chain, when `--mempool` is set.
+    mempool: Option<MempoolWatcher>,
 }
 
 impl Dex {
     pub fn new(
-        middleware: Arc<Provider<Http>>,
-        factory_address: Address,
-        router_address: Address,
+        config: &Config,
+        name: String,
+        contracts: &ContractAddresses,
         chain: String,
-        alert: bool,
-        verify: bool,
+        settings: Settings,
+        store: Option<Arc<Store>>,
     ) -> Self {
+        let middleware = config.http.clone();
+        let (factory_address, router_address) = (contracts.factory, contracts.router);
         let factory = UniV2Factory::new(factory_address, Arc::clone(&middleware));
         let router = UniV2Router::new(router_address, Arc::clone(&middleware));
 
-        let name = Self::get_dex_name(factory_address);
+        let known = known_dex(factory_address);
+        let name = contracts
+            .name
+            .clone()
+            .or_else(|| known.map(|dex| dex.name.to_string()))
+            .unwrap_or(name);
+        let website = contracts
+            .website
+            .clone()
+            .or_else(|| known.map(|dex| dex.website.to_string()));
+        let chart_slug = contracts
+            .chart_slug
+            .clone()
+            .unwrap_or_else(|| chain.to_lowercase());
 
         Self {
             chain,
             name,
             factory_address,
             router_address,
+            kind: contracts.kind,
+            init_code_hash: contracts.init_code_hash,
+            fee: contracts.fee,
+            start_block: contracts.start_block,
+            website,
+            logo: contracts.logo.clone(),
+            chart_slug,
+            store,
+            oracle: config.oracle.clone(),
+            quotes: config.quotes.clone(),
+            alerts: config.alerts.clone(),
+            events: config.events.clone(),
             factory,
             router,
-            alert,
-            verify,
+            alert: settings.alert,
+            verify: settings.verify,
+            rug: settings.rug,
+            lp_window: settings.lp_window,
+            filters: settings.filters,
+            watched: config.watched.clone(),
+            mempool: config.mempool.clone(),
+        }
+    }
+
+    /// Where the alert of a launch goes, `None` when alerts are off or the launch
+    /// doesn't pass the filters.
+    pub fn alert_route(&self, launch: &Launch) -> Option<Route> {
+        if !self.alert {
+            return None;
+        }
+        let filters = match &self.filters {
+            Some(filters) => filters,
+            None => return Some(Route::default()),
+        };
+        match filters.matching(launch) {
+            Some(rule) => {
+                timestamp_print!(
+                    Color::Green,
+                    Some(false),
+                    self.chain,
+                    format!("[FILTER] Matches \"{}\", alerting", rule.name)
+                );
+                Some(Route {
+                    rule: Some(rule.name.clone()),
+                    sinks: rule.sinks.clone(),
+                })
+            }
+            None => {
+                timestamp_print!(
+                    Color::Yellow,
+                    Some(false),
+                    self.chain,
+                    format!("[FILTER] No rule matches, alert skipped")
+                );
+                None
+            }
         }
     }
 
-    pub fn get_dex_name(contract_address: Address) -> String {
-        let address_str = format!("{:#066x}", contract_address);
+    /// Dexscreener chart of a pair or pool of this dex.
+    pub fn chart_url(&self, pair: Address) -> String {
+        format!("https://dexscreener.com/{}/{:#066x}", self.chart_slug, pair)
+    }
 
-        let dex_name = match address_str.as_str() {
-            UNISWAPV3_FACTORY => "Uniswap V3".to_string(),
-            UNISWAPV2_FACTORY => "Uniswap V2".to_string(),
-            SUSHISWAPV2_FACTORY => "Sushiswap V2".to_string(),
-            SUSHIV2_FACTORY_ARBITRUM => "Sushiswap V2".to_string(),
-            VVS_FACTORY => "VVS Finance".to_string(),
-            PANCAKESWAP_FACTORY => "Pancakeswap".to_string(),
-            TRADERJOE_FACTORY_ARBITRUM => "Trader Joe".to_string(),
-            _default => "UniswapV2 Like".to_string(),
+    /// Logs of the factory creation event matching the dex `kind`.
+    pub fn creation_filter(&self) -> Filter {
+        let signature = match self.kind {
+            DexKind::UniV2 => PairCreatedFilter::signature(),
+            DexKind::UniV3 => PoolCreatedFilter::signature(),
+            DexKind::Solidly => SolidlyPairCreatedFilter::signature(),
         };
-        dex_name
+        Filter::new().address(self.factory_address).topic0(signature)
     }
 
-    pub async fn token_stream_pairs_created(
-        self,
-        ws: Arc<Provider<Ws>>,
-        http: Arc<Provider<Http>>,
-        token_address: Address,
-    ) -> Result<()> {
-        println!(
-            "----------------------- {} | {} -----------------------",
-            self.name, self.chain
-        );
+    fn decode_created(&self, log: Log) -> Option<Created> {
+        match self.kind {
+            DexKind::UniV2 => parse_log::<PairCreatedFilter>(log).ok().map(|event| Created::Pair {
+                address: event.pair,
+                token0: event.token_0,
+                token1: event.token_1,
+            }),
+            DexKind::Solidly => parse_log::<SolidlyPairCreatedFilter>(log).ok().map(|event| Created::Pair {
+                address: event.pair,
+                token0: event.token_0,
+                token1: event.token_1,
+            }),
+            DexKind::UniV3 => parse_log::<PoolCreatedFilter>(log).ok().map(|event| Created::Pool {
+                address: event.pool,
+                token0: event.token_0,
+                token1: event.token_1,
+                fee: event.fee,
+                tick_spacing: event.tick_spacing,
+            }),
+        }
+    }
+
+    /// Replays the pairs created between two blocks through the same pipeline as the
+    /// live stream (tokens, first liquidity addition, reserves). Returns how many were found.
+    pub async fn backfill(self: Arc<Self>, http: Arc<Provider<Http>>, from_block: u64, to_block: u64) -> Result<usize> {
         timestamp_print!(
             Color::BrightBlue,
             Some(false),
+            self.chain,
             format!(
-                "[{}] Listening for PairCreated events, on token {} from {}",
-                token_address, self.name, self.factory_address
+                "[BACKFILL][{}] Fetching pairs created from block {} to {}, from {}",
+                self.name, from_block, to_block, self.factory_address
             )
         );
-        let contract = UniV2Factory::new(self.factory_address, ws.clone());
-        let events = contract.events();
-        let mut stream = events.stream().await?.with_meta();
+        let mut pager = LogPager::new(http.clone(), self.creation_filter(), from_block, to_block);
+        let mut found = 0;
+        while let Some(logs) = pager.next_page().await? {
+            for log in logs {
+                let block_number = log.block_number.unwrap_or_default();
+                let created = match self.decode_created(log) {
+                    Some(created) => created,
+                    None => continue,
+                };
+                let address = created.address();
+                if let Some(store) = &self.store {
+                    if store.is_known_pair(&self.chain, address).unwrap_or(false) {
+                        continue;
+                    }
+                }
+                found += 1;
 
+                let dex = self.clone();
+                let http = http.clone();
+                let to_block: U64 = to_block.into();
+                let pipeline = tokio::spawn(async move {
+                    let liquidity = match created {
+                        Created::Pair { address, token0, token1 } => {
+                            let pair = Pair::new(address, token0, token1, &dex, http.clone()).await?;
+                            dex.save_created(address, &pair.token0, &pair.token1, None, block_number);
+                            Arc::new(pair).backfill_add_liquidity(http, block_number, to_block).await
+                        }
+                        Created::Pool { address, token0, token1, fee, tick_spacing } => {
+                            let pool = Pool::new(address, token0, token1, fee, tick_spacing, &dex, http.clone()).await?;
+                            dex.save_created(address, &pool.token0, &pool.token1, Some((fee, tick_spacing)), block_number);
+                            Arc::new(pool).backfill_add_liquidity(http, block_number, to_block).await
+                        }
+                    };
+                    if let Ok(false) = liquidity {
+                        timestamp_print!(
+                            Color::Yellow,
+                            Some(false),
+                            dex.chain,
+                            format!("[BACKFILL][{}] No liquidity added on {:#066x}", dex.name, address)
+                        );
+                    }
+                    liquidity
+                });
+                match pipeline.await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(err)) => {
+                        timestamp_print!(Color::Red, Some(false), self.chain, format!("[BACKFILL][{}] {:#066x}: {}", self.name, address, err));
+                    }
+                    Err(err) => {
+                        timestamp_print!(Color::Red, Some(false), self.chain, format!("[BACKFILL][{}] {:#066x}: {}", self.name, address, err));
+                    }
+                }
+            }
+            print_progress(&self.chain, &self.name, &pager, to_block, found);
+        }
+        Ok(found)
+    }
+
+    /// Profiles the wallet that sent the liquidity addition of `pair` and records it
+    /// as the pair deployer. `None` when the transaction of `meta` can't be read.
+    pub async fn profile_deployer(&self, http: Arc<Provider<Http>>, pair: Address, meta: &LogMeta) -> Option<WalletProfile> {
+        let transaction = http.get_transaction(meta.transaction_hash).await.ok()??;
+        let profile = WalletProfile::new(http, self, transaction.from, pair, meta.block_number).await;
+        timestamp_print!(
+            if profile.rugs.unwrap_or(0) > 0 { Color::Red } else { Color::Green },
+            Some(false),
+            self.chain,
+            format!("[DEPLOYER] {}", profile)
+        );
+        self.persist(|store| store.save_deployer(&self.chain, pair, transaction.from));
+        Some(profile)
+    }
+
+    /// Stores a new pair or pool with its tokens, and publishes it.
+    fn save_created(&self, address: Address, token0: &Token, token1: &Token, pool: Option<(u32, i32)>, block_number: U64) {
+        self.events.publish(TrackerEvent::PairCreated {
+            chain: self.chain.clone(),
+            dex: self.name.clone(),
+            pair: address,
+            token0: token0.into(),
+            token1: token1.into(),
+            fee: pool.map(|(fee, _)| fee),
+            block: block_number,
+        });
+        self.persist(|store| {
+            store.save_token(&self.chain, token0)?;
+            store.save_token(&self.chain, token1)?;
+            store.save_pair(&PairRecord {
+                chain: self.chain.clone(),
+                factory: self.factory_address,
+                address,
+                token0: token0.address,
+                token1: token1.address,
+                fee: pool.map(|(fee, _)| fee),
+                tick_spacing: pool.map(|(_, tick_spacing)| tick_spacing),
+                created_block: block_number,
+            })
+        });
+    }
+
+    /// Runs `f` on the store when `--db` is set, failures are logged and otherwise ignored.
+    pub fn persist<F>(&self, f: F)
+    where
+        F: FnOnce(&Store) -> Result<()>,
+    {
+        if let Some(store) = &self.store {
+            if let Err(err) = f(store) {
+                timestamp_print!(Color::Red, Some(false), self.chain, format!("[DB] {}", err));
+            }
+        }
+    }
+
+    /// Streams the factory creation events of the dex.
+    pub async fn stream(self, logs: LogSubscriber, http: Arc<Provider<Http>>) -> Result<()> {
+        self.persist(|store| store.save_dex(&self));
         let dex = Arc::new(self);
-        while let Some(Ok((event, meta))) = stream.next().await {
-            if event.token_0 == token_address || event.token_1 == token_address {
-                timestamp_print!(
-                    Color::Green,
-                    Some(false),
-                    format!(
-                        "[PAIR][{}] PairCreated detected: {:#066x}",
-                        dex.name, event.pair
+        let retractions = tokio::spawn(Self::follow_retractions(dex.clone(), logs.clone(), http.clone()));
+        let mempool = tokio::spawn(Self::follow_mempool(dex.clone(), http.clone()));
+        Self::resume_pending(&dex, logs.clone(), http.clone()).await;
+
+        let result = dex.stream_created(logs, http).await;
+        retractions.abort();
+        mempool.abort();
+        result
+    }
+
+    /// Watches again the stored pairs that had not received liquidity before the last
+    /// shutdown, or the last restart of the dex stream, unless they are still watched.
+    async fn resume_pending(dex: &Arc<Self>, logs: LogSubscriber, http: Arc<Provider<Http>>) {
+        let store = match &dex.store {
+            Some(store) => store,
+            None => return,
+        };
+        let pending = match store.pending_pairs(&dex.chain, dex.factory_address) {
+            Ok(pending) => pending
+                .into_iter()
+                .filter(|pair| !dex.watched.is_watching(pair.address))
+                .collect::<Vec<_>>(),
+            Err(err) => {
+                timestamp_print!(Color::Red, Some(false), dex.chain, format!("[DB] {}", err));
+                return;
+            }
+        };
+        if !pending.is_empty() {
+            timestamp_print!(
+                Color::BrightBlue,
+                Some(false),
+                dex.chain,
+                format!("[{}] Resuming {} pairs waiting for liquidity", dex.name, pending.len())
+            );
+        }
+        for pair in pending {
+            let resumed = match dex.kind {
+                DexKind::UniV3 => {
+                    Self::on_pool_created(
+                        dex,
+                        pair.address,
+                        pair.token0,
+                        pair.token1,
+                        pair.fee.unwrap_or_default(),
+                        pair.tick_spacing.unwrap_or_default(),
+                        pair.created_block,
+                        logs.clone(),
+                        http.clone(),
+                    )
+                    .await
+                }
+                _ => {
+                    Self::on_pair_created(
+                        dex,
+                        pair.address,
+                        pair.token0,
+                        pair.token1,
+                        pair.created_block,
+                        logs.clone(),
+                        http.clone(),
                     )
+                    .await
+                }
+            };
+            if let Err(err) = resumed {
+                dex.report_dropped(pair.address, err);
+            }
+        }
+    }
+
+    /// Starts watching a pair or pool created at `block_number`.
+    async fn on_created(
+        dex: &Arc<Self>,
+        created: Created,
+        block_number: U64,
+        logs: LogSubscriber,
+        http: Arc<Provider<Http>>,
+    ) -> Result<()> {
+        match created {
+            Created::Pair { address, token0, token1 } => {
+                Self::on_pair_created(dex, address, token0, token1, block_number, logs, http).await
+            }
+            Created::Pool { address, token0, token1, fee, tick_spacing } => {
+                Self::on_pool_created(dex, address, token0, token1, fee, tick_spacing, block_number, logs, http).await
+            }
+        }
+    }
+
+    /// Fetches the pair tokens and waits for its first liquidity addition in a new task.
+    /// Fails when a token can't be read, e.g. it isn't an ERC20.
+    async fn on_pair_created(
+        dex: &Arc<Self>,
+        pair_address: Address,
+        token0: Address,
+        token1: Address,
+        block_number: U64,
+        logs: LogSubscriber,
+        http: Arc<Provider<Http>>,
+    ) -> Result<()> {
+        let pair = Pair::new(pair_address, token0, token1, dex, http.clone()).await?;
+        dex.save_created(pair_address, &pair.token0, &pair.token1, None, block_number);
+
+        let mut pair = Arc::new(pair);
+        let task = tokio::spawn(async move {
+            if let Err(err) = pair.stream_add_liquidity(logs, http, block_number).await {
+                pair.dex.report_dropped(pair.address, err);
+            }
+        });
+        let created = Created::Pair {
+            address: pair_address,
+            token0,
+            token1,
+        };
+        dex.watched.insert(created, block_number, task);
+        Ok(())
+    }
+
+    /// Fetches the pool tokens and waits for its first position in a new task.
+    /// Fails when a token can't be read.
+    #[allow(clippy::too_many_arguments)]
+    async fn on_pool_created(
+        dex: &Arc<Self>,
+        pool_address: Address,
+        token0: Address,
+        token1: Address,
+        fee: u32,
+        tick_spacing: i32,
+        block_number: U64,
+        logs: LogSubscriber,
+        http: Arc<Provider<Http>>,
+    ) -> Result<()> {
+        let pool = Pool::new(pool_address, token0, token1, fee, tick_spacing, dex, http.clone()).await?;
+        dex.save_created(pool_address, &pool.token0, &pool.token1, Some((fee, tick_spacing)), block_number);
+
+        let mut pool = Arc::new(pool);
+        let task = tokio::spawn(async move {
+            if let Err(err) = pool.stream_add_liquidity(logs, http, block_number).await {
+                pool.dex.report_dropped(pool.address, err);
+            }
+        });
+        let created = Created::Pool {
+            address: pool_address,
+            token0,
+            token1,
+            fee,
+            tick_spacing,
+        };
+        dex.watched.insert(created, block_number, task);
+        Ok(())
+    }
+
+    /// Logs a pair or pool that is no longer followed because of `err`.
+    fn report_dropped(&self, address: Address, err: eyre::Report) {
+        timestamp_print!(
+            Color::Red,
+            Some(false),
+            self.chain,
+            format!("[{}] {:#066x} dropped: {:#}", self.name, address, err)
+        );
+    }
+
+    /// Undoes what was done for the logs of the dex that were reorged out: a pair
+    /// whose creation is gone is forgotten, a pair whose first liquidity is gone is
+    /// watched again from its creation, and the alerts of both are retracted.
+    async fn follow_retractions(dex: Arc<Self>, logs: LogSubscriber, http: Arc<Provider<Http>>) {
+        let mut retractions = logs.retractions();
+        loop {
+            let log = match retractions.recv().await {
+                Ok(log) => log,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    timestamp_print!(Color::Red, Some(false), dex.chain, format!("[REORG][{}] {} retractions missed", dex.name, skipped));
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            let (block_number, tx_hash) = (log.block_number.unwrap_or_default(), log.transaction_hash.unwrap_or_default());
+            if log.address == dex.factory_address {
+                let address = match dex.decode_created(log) {
+                    Some(created) => created.address(),
+                    None => continue,
+                };
+                timestamp_print!(
+                    Color::Red,
+                    Some(true),
+                    dex.chain,
+                    format!("[REORG][{}] Creation of {:#066x} reorged out of block {}, pair dropped", dex.name, address, block_number)
                 );
-                let pair =
-                    Pair::new(event.pair, event.token_0, event.token_1, &dex, http.clone()).await;
-
-                let mut pair = Arc::new(pair);
-                let ws_clone = ws.clone();
-                let http_clone = http.clone();
-                tokio::spawn(async move {
-                    pair.stream_add_liquidity(ws_clone, http_clone, meta.block_number)
-                        .await
-                        .unwrap();
-                });
+                dex.watched.remove(address);
+                dex.persist(|store| store.delete_pair(&dex.chain, address));
+                dex.publish_reorged(address, block_number, tx_hash);
+                dex.alerts.retract_pair(address);
+                continue;
+            }
+
+            let (created, created_block) = match dex.watched.get(log.address) {
+                Some(watched) => watched,
+                None => continue,
+            };
+            let topic = log.topics.first().copied().unwrap_or_default();
+            let first_liquidity = topic == MintFilter::signature() || topic == PoolMintFilter::signature();
+            timestamp_print!(
+                Color::Red,
+                Some(true),
+                dex.chain,
+                format!(
+                    "[REORG][{}] {} of {:#066x} reorged out of block {}",
+                    dex.name,
+                    if first_liquidity { "Liquidity addition" } else { "Event" },
+                    log.address,
+                    block_number
+                )
+            );
+            if let Some(log_index) = log.log_index {
+                dex.persist(|store| store.delete_liquidity_event(&dex.chain, tx_hash, log_index));
+            }
+            if first_liquidity {
+                dex.persist(|store| store.reset_launch(&dex.chain, log.address));
+            } else if topic == BurnFilter::signature() {
+                dex.persist(|store| store.unmark_rugged(&dex.chain, log.address));
+            }
+            dex.publish_reorged(log.address, block_number, tx_hash);
+            dex.alerts.retract(log.address, tx_hash);
+            if first_liquidity {
+                if let Err(err) = Self::on_created(&dex, created, created_block, logs.clone(), http.clone()).await {
+                    dex.report_dropped(log.address, err);
+                }
+            }
+        }
+    }
+
+    /// Checks a token on the chain explorer and publishes the result. A check that
+    /// fails is logged and leaves the verification unknown, `None`.
+    pub async fn verify_token(&self, token: &mut Token) -> Option<bool> {
+        match token.verified_contract(self.chain.clone()).await {
+            Ok(verified) => {
+                self.publish_verified(token.address, verified);
+                Some(verified)
+            }
+            Err(err) => {
                 timestamp_print!(
-                    Color::BrightBlue,
+                    Color::Yellow,
                     Some(false),
-                    format!(
-                        "[PAIR][{}] Listening for new PairCreated events, from {}",
-                        dex.name, dex.factory_address
-                    )
+                    self.chain,
+                    format!("[TOKEN] {} | Verification failed: {:#}", token.name, err)
                 );
+                None
             }
         }
+    }
+
+    /// Publishes the verification of a token on the chain explorer.
+    fn publish_verified(&self, token: Address, verified: bool) {
+        self.events.publish(TrackerEvent::TokenVerified {
+            chain: self.chain.clone(),
+            token,
+            verified,
+        });
+    }
+
+    /// Publishes the risk scan of a token.
+    pub fn publish_risk(&self, token: &Token) {
+        if let Some(report) = &token.risk {
+            self.events.publish(TrackerEvent::RiskReport {
+                chain: self.chain.clone(),
+                token: token.address,
+                report: report.clone(),
+            });
+        }
+    }
+
+    fn publish_reorged(&self, pair: Address, block: U64, tx_hash: H256) {
+        self.events.publish(TrackerEvent::Reorged {
+            chain: self.chain.clone(),
+            dex: self.name.clone(),
+            pair,
+            block,
+            tx_hash,
+        });
+    }
+
+    /// Alerts the liquidity sent to the router for a pair that doesn't exist yet or
+    /// has none, before the transaction is mined. Only UniswapV2 routers are decoded.
+    async fn follow_mempool(dex: Arc<Self>, http: Arc<Provider<Http>>) {
+        let mut pending = match (&dex.mempool, dex.kind) {
+            (Some(mempool), DexKind::UniV2) => mempool.subscribe(),
+            _ => return,
+        };
+        timestamp_print!(
+            Color::BrightBlue,
+            Some(false),
+            dex.chain,
+            format!("[MEMPOOL][{}] Listening for pending liquidity additions, to {}", dex.name, dex.router_address)
+        );
+        loop {
+            let liquidity = match pending.recv().await {
+                Ok(liquidity) => liquidity,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    timestamp_print!(Color::Yellow, Some(false), dex.chain, format!("[MEMPOOL][{}] {} pending transactions skipped", dex.name, skipped));
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            if liquidity.router != dex.router_address {
+                continue;
+            }
+            // Each addition is checked apart, a token that can't be read only loses its own.
+            let (dex, http) = (dex.clone(), http.clone());
+            tokio::spawn(async move {
+                if let Err(err) = dex.on_pending_liquidity(&liquidity, http).await {
+                    timestamp_print!(
+                        Color::Yellow,
+                        Some(false),
+                        dex.chain,
+                        format!("[MEMPOOL][{}] {:#066x} skipped: {}", dex.name, liquidity.hash, err)
+                    );
+                }
+            });
+        }
+    }
+
+    async fn on_pending_liquidity(&self, liquidity: &PendingLiquidity, http: Arc<Provider<Http>>) -> Result<()> {
+        let token_b = match liquidity.token_b {
+            Some(token_b) => token_b,
+            None => self.router.weth().call().await?,
+        };
+        let ((address0, raw0), (address1, raw1)) = if liquidity.token_a < token_b {
+            ((liquidity.token_a, liquidity.amount_a), (token_b, liquidity.amount_b))
+        } else {
+            ((token_b, liquidity.amount_b), (liquidity.token_a, liquidity.amount_a))
+        };
+
+        let existing = self.factory.get_pair(address0, address1).call().await?;
+        let new_pair = existing.is_zero();
+        let pair = if new_pair {
+            match self.init_code_hash {
+                Some(init_code_hash) => pair_address(self.factory_address, address0, address1, init_code_hash),
+                // Without the pair address there is no chart nor pair to point to.
+                None => return Err(eyre::eyre!("new pair, its address needs the init_code_hash of the dex")),
+            }
+        } else {
+            if !LpPair::new(existing, http.clone()).total_supply().call().await?.is_zero() {
+                // Liquidity added to a pair already trading, not a launch.
+                return Ok(());
+            }
+            existing
+        };
+
+        let mut token0 = Token::new(address0, http.clone()).await?;
+        let mut token1 = Token::new(address1, http.clone()).await?;
+        let quote = self.quotes.classify(address0, address1);
+        if self.verify {
+            for token in quote.unknown_tokens(&mut token0, &mut token1) {
+                self.verify_token(token).await;
+            }
+        }
+        let amount0 = u256_amount(raw0, token0.decimals);
+        let amount1 = u256_amount(raw1, token1.decimals);
+
+        timestamp_print!(
+            Color::Magenta,
+            Some(true),
+            self.chain,
+            format!(
+                "[MEMPOOL][{}] Pending liquidity for {}pair {}/{}: {:#066x}",
+                self.name,
+                if new_pair { "new " } else { "" },
+                token0.symbol,
+                token1.symbol,
+                liquidity.hash
+            )
+        );
+        timestamp_print!(
+            Color::Magenta,
+            Some(false),
+            self.chain,
+            format!("[MEMPOOL][LIQ] {:?} {} / {:?} {}, from {:#x}", amount0, token0.symbol, amount1, token1.symbol, liquidity.sender)
+        );
+        let value = match &self.oracle {
+            Some(oracle) => {
+                let block = http.get_block_number().await?;
+                oracle.liquidity_value(address0, amount0, address1, amount1, block).await
+            }
+            None => None,
+        };
+        if let Some(value) = value {
+            timestamp_print!(Color::Magenta, Some(false), self.chain, format!("[MEMPOOL][LIQ] Value: ${:.2}", value.total()));
+        }
+
+        self.events.publish(TrackerEvent::PendingLiquidity {
+            chain: self.chain.clone(),
+            dex: self.name.clone(),
+            pair,
+            new_pair,
+            token0: TokenMeta::from(&token0),
+            token1: TokenMeta::from(&token1),
+            raw_amounts: (raw0, raw1),
+            amounts: (amount0, amount1),
+            value_usd: value.map(|value| value.total()),
+            sender: liquidity.sender,
+            gas_price: liquidity.gas_price,
+            deadline: liquidity.deadline,
+            tx_hash: liquidity.hash,
+        });
+        let launch = Launch {
+            dex: self,
+            token0: &token0,
+            token1: &token1,
+            quote,
+            amounts: (amount0, amount1),
+            value,
+            lp: None,
+            deployer: None,
+        };
+        if let Some(route) = self.alert_route(&launch) {
+            self.alerts.dispatch(LiquidityEvent::pending(&launch, pair, liquidity, new_pair), &route);
+        }
         Ok(())
     }
 
-    /// Streams the "PairCreated" event from the `factory_address`.
-    pub async fn stream_pairs_created(
-        self,
-        ws: Arc<Provider<Ws>>,
-        http: Arc<Provider<Http>>,
-    ) -> Result<()> {
-        println!(
+    /// Streams the factory creation events, decoded according to the dex `kind`.
+    async fn stream_created(self: Arc<Self>, logs: LogSubscriber, http: Arc<Provider<Http>>) -> Result<()> {
+        let (label, event) = match self.kind {
+            DexKind::UniV3 => ("POOL", "PoolCreated"),
+            DexKind::UniV2 | DexKind::Solidly => ("PAIR", "PairCreated"),
+        };
+        crate::console_println!(
             "----------------------- {} | {} -----------------------",
             self.name, self.chain
         );
         timestamp_print!(
             Color::BrightBlue,
             Some(false),
+            self.chain,
             format!(
-                "[{}] Listening for PairCreated events, from {}",
-                self.name, self.factory_address
+                "[{}] Listening for {} events, from {}",
+                self.name, event, self.factory_address
             )
         );
-        let contract = UniV2Factory::new(self.factory_address, ws.clone());
-        let events = contract.events();
-        let mut stream = events.stream().await?.with_meta();
+        let mut stream = logs.subscribe(self.creation_filter(), None);
 
-        let dex = Arc::new(self);
-        while let Some(Ok((event, meta))) = stream.next().await {
+        let dex = self;
+        while let Some(log) = stream.recv().await {
+            let block_number = log.block_number.unwrap_or_default();
+            let created = match dex.decode_created(log) {
+                Some(created) => created,
+                None => continue,
+            };
             timestamp_print!(
                 Color::Green,
                 Some(false),
+                dex.chain,
                 format!(
-                    "[PAIR][{}] PairCreated detected: {:#066x}",
-                    dex.name, event.pair
+                    "[{}][{}] {} detected: {:#066x}",
+                    label, dex.name, event, created.address()
                 )
             );
-            let pair =
-                Pair::new(event.pair, event.token_0, event.token_1, &dex, http.clone()).await;
-
-            let mut pair = Arc::new(pair);
-            let ws_clone = ws.clone();
-            let http_clone = http.clone();
-            tokio::spawn(async move {
-                pair.stream_add_liquidity(ws_clone, http_clone, meta.block_number)
-                    .await
-                    .unwrap();
-            });
+            if let Err(err) = Self::on_created(&dex, created, block_number, logs.clone(), http.clone()).await {
+                dex.report_dropped(created.address(), err);
+            }
             timestamp_print!(
                 Color::BrightBlue,
                 Some(false),
+                dex.chain,
                 format!(
-                    "[PAIR][{}] Listening for new PairCreated events, from {}",
-                    dex.name, dex.factory_address
+                    "[{}][{}] Listening for new {} events, from {}",
+                    label, dex.name, event, dex.factory_address
                 )
             );
         }
@@ -0,0 +1,240 @@
+//! Typed events of the tracker, published on a bus shared by every chain. The
+//! streaming code (block tracker, dexes, pairs, pools, mempool) publishes them and
+//! the read-only consumers (block banner, JSON output...) subscribe on their own,
+//! so such a consumer never needs the streaming code to change.
+//!
+//! The alert dispatcher and the store are still called by the streaming code: the
+//! bus drops the oldest events for a consumer that falls behind, which a database
+//! row or an alert can't afford, and both need what the events don't carry (the
+//! route of a filtered alert, the deployer, the LP holders, the log index to undo).
+
+use ethers::prelude::*;
+use serde::{Serialize, Serializer};
+use tokio::sync::broadcast;
+
+use crate::block_scanner::BlockHeader;
+use crate::dex::Dex;
+use crate::helpers::u256_amount;
+use crate::risk::RiskReport;
+use crate::token::Token;
+
+/// Events kept for a consumer that falls behind.
+const CAPACITY: usize = 1024;
+
+/// A token of a pair, as read from its contract.
+#[derive(Clone, Debug, Serialize)]
+pub struct TokenMeta {
+    pub address: Address,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl From<&Token> for TokenMeta {
+    fn from(token: &Token) -> Self {
+        Self {
+            address: token.address,
+            name: token.name.clone(),
+            symbol: token.symbol.clone(),
+            decimals: token.decimals,
+        }
+    }
+}
+
+/// Liquidity added to or removed from a pair or pool by a log.
+#[derive(Clone, Debug, Serialize)]
+pub struct LiquidityChange {
+    pub chain: String,
+    pub dex: String,
+    pub pair: Address,
+    pub token0: TokenMeta,
+    pub token1: TokenMeta,
+    /// Amounts in the token smallest units.
+    #[serde(serialize_with = "decimal_pair")]
+    pub raw_amounts: (U256, U256),
+    /// Amounts scaled by the token decimals.
+    pub amounts: (f64, f64),
+    /// Reserves after the change, when read.
+    pub reserves: Option<(f64, f64)>,
+    pub value_usd: Option<f64>,
+    #[serde(serialize_with = "as_number")]
+    pub block: U64,
+    pub tx_hash: H256,
+    #[serde(serialize_with = "as_small_number")]
+    pub log_index: U256,
+}
+
+impl LiquidityChange {
+    /// Change made to `pair` by the log of `meta`, without reserves nor value.
+    pub fn new(dex: &Dex, pair: Address, token0: &Token, token1: &Token, raw_amounts: (U256, U256), meta: &LogMeta) -> Self {
+        Self {
+            chain: dex.chain.clone(),
+            dex: dex.name.clone(),
+            pair,
+            token0: token0.into(),
+            token1: token1.into(),
+            raw_amounts,
+            amounts: (
+                u256_amount(raw_amounts.0, token0.decimals),
+                u256_amount(raw_amounts.1, token1.decimals),
+            ),
+            reserves: None,
+            value_usd: None,
+            block: meta.block_number,
+            tx_hash: meta.transaction_hash,
+            log_index: meta.log_index,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum TrackerEvent {
+    BlockSeen {
+        chain: String,
+        #[serde(serialize_with = "as_number")]
+        number: U64,
+        hash: H256,
+        #[serde(serialize_with = "as_small_number")]
+        timestamp: U256,
+    },
+    /// A pair or pool created by a dex factory, `fee` being the one of a pool.
+    PairCreated {
+        chain: String,
+        dex: String,
+        pair: Address,
+        token0: TokenMeta,
+        token1: TokenMeta,
+        fee: Option<u32>,
+        #[serde(serialize_with = "as_number")]
+        block: U64,
+    },
+    LiquidityAdded(LiquidityChange),
+    LiquidityRemoved(LiquidityChange),
+    /// Liquidity about to be added by a pending transaction.
+    PendingLiquidity {
+        chain: String,
+        dex: String,
+        /// Computed from the dex `init_code_hash` when the pair doesn't exist yet.
+        pair: Address,
+        new_pair: bool,
+        token0: TokenMeta,
+        token1: TokenMeta,
+        #[serde(serialize_with = "decimal_pair")]
+        raw_amounts: (U256, U256),
+        amounts: (f64, f64),
+        value_usd: Option<f64>,
+        sender: Address,
+        /// In wei.
+        #[serde(serialize_with = "decimal_option")]
+        gas_price: Option<U256>,
+        #[serde(serialize_with = "decimal")]
+        deadline: U256,
+        tx_hash: H256,
+    },
+    TokenVerified {
+        chain: String,
+        token: Address,
+        verified: bool,
+    },
+    RiskReport {
+        chain: String,
+        token: Address,
+        report: RiskReport,
+    },
+    /// A log of a pair or of its factory was reorged out.
+    Reorged {
+        chain: String,
+        dex: String,
+        pair: Address,
+        #[serde(serialize_with = "as_number")]
+        block: U64,
+        tx_hash: H256,
+    },
+}
+
+impl TrackerEvent {
+    pub fn block_seen(chain: &str, header: &BlockHeader) -> Self {
+        TrackerEvent::BlockSeen {
+            chain: chain.to_string(),
+            number: header.number,
+            hash: header.hash,
+            timestamp: header.timestamp,
+        }
+    }
+
+    /// Block of the log behind the event, `None` for a block or a pending transaction.
+    pub fn block(&self) -> Option<U64> {
+        match self {
+            TrackerEvent::PairCreated { block, .. } | TrackerEvent::Reorged { block, .. } => Some(*block),
+            TrackerEvent::LiquidityAdded(change) | TrackerEvent::LiquidityRemoved(change) => Some(change.block),
+            _ => None,
+        }
+    }
+
+    pub fn chain(&self) -> &str {
+        match self {
+            TrackerEvent::BlockSeen { chain, .. }
+            | TrackerEvent::PairCreated { chain, .. }
+            | TrackerEvent::PendingLiquidity { chain, .. }
+            | TrackerEvent::TokenVerified { chain, .. }
+            | TrackerEvent::RiskReport { chain, .. }
+            | TrackerEvent::Reorged { chain, .. } => chain,
+            TrackerEvent::LiquidityAdded(change) | TrackerEvent::LiquidityRemoved(change) => &change.chain,
+        }
+    }
+}
+
+/// Block numbers, timestamps and log indexes are JSON numbers rather than hex strings.
+fn as_number<S: Serializer>(value: &U64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(value.as_u64())
+}
+
+fn as_small_number<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(value.low_u64())
+}
+
+/// Token amounts are decimal strings, a JSON number can't hold them without loss.
+fn decimal<S: Serializer>(value: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+fn decimal_option<S: Serializer>(value: &Option<U256>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serializer.collect_str(value),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn decimal_pair<S: Serializer>(value: &(U256, U256), serializer: S) -> Result<S::Ok, S::Error> {
+    [value.0.to_string(), value.1.to_string()].serialize(serializer)
+}
+
+/// Broadcast channel of the tracker events, cheap to clone.
+#[derive(Clone, Debug)]
+pub struct EventBus {
+    events: broadcast::Sender<TrackerEvent>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (events, _) = broadcast::channel(CAPACITY);
+        Self { events }
+    }
+
+    pub fn publish(&self, event: TrackerEvent) {
+        // No consumer is not an error, the tracking goes on.
+        let _ = self.events.send(event);
+    }
+
+    /// Events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<TrackerEvent> {
+        self.events.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
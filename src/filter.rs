@@ -0,0 +1,485 @@
+//! Rules deciding which launches are worth an alert, loaded from a JSON file:
+//!
+//! ```json
+//! {
+//!     "rules": [
+//!         {
+//!             "name": "Verified launches with real liquidity",
+//!             "when": { "all": [
+//!                 { "field": "liquidity_usd", "op": ">=", "value": 20000 },
+//!                 { "field": "verified", "op": "==", "value": true },
+//!                 { "not": { "field": "token_name", "op": "matches", "value": "(?i)test" } }
+//!             ] }
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! A launch is alerted when any rule matches. Conditions are combined with
+//! `all`, `any` and `not`, one of them per condition; a comparison on a value
+//! that is unknown for the launch (no price reference, no simulation...) is false.
+//! A `not` over it is then true: `{ "not": { "field": "sell_tax", "op": ">", "value": 10 } }`
+//! also lets through the launches that couldn't be simulated, while
+//! `{ "field": "sell_tax", "op": "<=", "value": 10 }` only keeps the simulated ones.
+
+use std::fmt;
+use std::fs;
+
+use eyre::{eyre, Result, WrapErr};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value as Json;
+
+use crate::alert::SINKS;
+use crate::dex::Dex;
+use crate::lp::LpDistribution;
+use crate::oracle::LiquidityValue;
+use crate::quote::{PairQuote, QuoteKind, Side};
+use crate::token::Token;
+use crate::wallet::WalletProfile;
+
+/// Everything a rule can look at when liquidity is first added to a pair or pool.
+pub struct Launch<'a> {
+    pub dex: &'a Dex,
+    pub token0: &'a Token,
+    pub token1: &'a Token,
+    pub quote: PairQuote,
+    /// Liquidity added, in token0 and token1.
+    pub amounts: (f64, f64),
+    pub value: Option<LiquidityValue>,
+    pub lp: Option<LpDistribution>,
+    pub deployer: Option<&'a WalletProfile>,
+}
+
+impl<'a> Launch<'a> {
+    /// The launched token, token0 when it can't be told apart.
+    fn base(&self) -> &'a Token {
+        match self.quote.base() {
+            Some(Side::Token1) => self.token1,
+            _ => self.token0,
+        }
+    }
+
+    fn quote_token(&self) -> Option<&'a Token> {
+        match self.quote.base()? {
+            Side::Token0 => Some(self.token1),
+            Side::Token1 => Some(self.token0),
+        }
+    }
+
+    fn native_amount(&self) -> Option<f64> {
+        match self.quote {
+            PairQuote::Base { base: Side::Token0, quote: QuoteKind::Native } => Some(self.amounts.1),
+            PairQuote::Base { base: Side::Token1, quote: QuoteKind::Native } => Some(self.amounts.0),
+            _ => None,
+        }
+    }
+
+    fn get(&self, field: Field) -> Option<Value> {
+        let base = self.base();
+        let simulation = base.simulation.as_ref();
+        let percent = |share: f64| Value::Number(share * 100.0);
+        match field {
+            Field::Chain => Some(Value::Text(self.dex.chain.clone())),
+            Field::Dex => Some(Value::Text(self.dex.name.clone())),
+            Field::DexKind => Some(Value::Text(self.dex.kind.to_string())),
+            Field::Quote => self.quote_token().map(|token| Value::Text(token.symbol.clone())),
+            Field::QuoteKind => Some(Value::Text(
+                match self.quote {
+                    PairQuote::Base { quote: QuoteKind::Native, .. } => "native",
+                    PairQuote::Base { quote: QuoteKind::Stable, .. } => "stable",
+                    PairQuote::Base { quote: QuoteKind::Extra, .. } => "extra",
+                    PairQuote::Unquoted => "none",
+                    PairQuote::QuoteOnly => "both",
+                }
+                .to_string(),
+            )),
+            Field::TokenName => Some(Value::Text(base.name.clone())),
+            Field::TokenSymbol => Some(Value::Text(base.symbol.clone())),
+            Field::LiquidityUsd => self.value.map(|value| Value::Number(value.total())),
+            Field::LiquidityNative => self.native_amount().map(Value::Number),
+            Field::Verified => base.verified.map(Value::Bool),
+            Field::BuyTax => simulation.and_then(|simulation| simulation.buy_tax).map(percent),
+            Field::SellTax => simulation.and_then(|simulation| simulation.sell_tax).map(percent),
+            Field::TransferTax => simulation.and_then(|simulation| simulation.transfer_tax).map(percent),
+            Field::Honeypot => simulation.map(|simulation| Value::Bool(simulation.is_honeypot())),
+            Field::RiskScore => base.risk.as_ref().map(|risk| Value::Number(risk.score as f64)),
+            Field::LpLocked => self.lp.map(|lp| percent(lp.locked)),
+            Field::LpBurned => self.lp.map(|lp| percent(lp.burned)),
+            Field::LpDeployer => self.lp.map(|lp| percent(lp.deployer)),
+            Field::DeployerAgeDays => self.deployer?.age_days.map(Value::Number),
+            Field::DeployerBalance => self.deployer?.balance.map(Value::Number),
+            Field::DeployerLaunches => self.deployer?.launches.map(|launches| Value::Number(launches as f64)),
+            Field::DeployerRugs => self.deployer?.rugs.map(|rugs| Value::Number(rugs as f64)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Number(f64),
+    Bool(bool),
+    Text(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    Number,
+    Bool,
+    Text,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "a number"),
+            Type::Bool => write!(f, "a boolean"),
+            Type::Text => write!(f, "a string"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Field {
+    Chain,
+    Dex,
+    DexKind,
+    Quote,
+    QuoteKind,
+    TokenName,
+    TokenSymbol,
+    LiquidityUsd,
+    LiquidityNative,
+    Verified,
+    BuyTax,
+    SellTax,
+    TransferTax,
+    Honeypot,
+    RiskScore,
+    LpLocked,
+    LpBurned,
+    LpDeployer,
+    DeployerAgeDays,
+    DeployerBalance,
+    DeployerLaunches,
+    DeployerRugs,
+}
+
+const FIELDS: [(&str, Field, Type); 22] = [
+    ("chain", Field::Chain, Type::Text),
+    ("dex", Field::Dex, Type::Text),
+    ("dex_kind", Field::DexKind, Type::Text),
+    ("quote", Field::Quote, Type::Text),
+    ("quote_kind", Field::QuoteKind, Type::Text),
+    ("token_name", Field::TokenName, Type::Text),
+    ("token_symbol", Field::TokenSymbol, Type::Text),
+    ("liquidity_usd", Field::LiquidityUsd, Type::Number),
+    ("liquidity_native", Field::LiquidityNative, Type::Number),
+    ("verified", Field::Verified, Type::Bool),
+    ("buy_tax", Field::BuyTax, Type::Number),
+    ("sell_tax", Field::SellTax, Type::Number),
+    ("transfer_tax", Field::TransferTax, Type::Number),
+    ("honeypot", Field::Honeypot, Type::Bool),
+    ("risk_score", Field::RiskScore, Type::Number),
+    ("lp_locked", Field::LpLocked, Type::Number),
+    ("lp_burned", Field::LpBurned, Type::Number),
+    ("lp_deployer", Field::LpDeployer, Type::Number),
+    ("deployer_age_days", Field::DeployerAgeDays, Type::Number),
+    ("deployer_balance", Field::DeployerBalance, Type::Number),
+    ("deployer_launches", Field::DeployerLaunches, Type::Number),
+    ("deployer_rugs", Field::DeployerRugs, Type::Number),
+];
+
+#[derive(Clone, Copy, Debug)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Matches,
+}
+
+impl Op {
+    fn parse(op: &str) -> Option<Self> {
+        Some(match op {
+            "==" => Op::Eq,
+            "!=" => Op::Ne,
+            ">" => Op::Gt,
+            ">=" => Op::Ge,
+            "<" => Op::Lt,
+            "<=" => Op::Le,
+            "matches" => Op::Matches,
+            _ => return None,
+        })
+    }
+
+    fn applies_to(&self, kind: Type) -> bool {
+        match self {
+            Op::Eq | Op::Ne => true,
+            Op::Gt | Op::Ge | Op::Lt | Op::Le => kind == Type::Number,
+            Op::Matches => kind == Type::Text,
+        }
+    }
+}
+
+/// Keys of a condition combining others, only one of them per condition.
+const COMBINATORS: [&str; 3] = ["all", "any", "not"];
+/// Keys of a condition comparing a field.
+const COMPARISON: [&str; 3] = ["field", "op", "value"];
+
+/// A validated condition.
+enum Condition {
+    All(Vec<Condition>),
+    Any(Vec<Condition>),
+    Not(Box<Condition>),
+    Compare { field: Field, op: Op, value: Value },
+    Matches { field: Field, regex: Regex },
+}
+
+impl Condition {
+    /// Checks a condition of the file, `path` locates it in the error messages.
+    fn parse(json: &Json, path: &str) -> Result<Self> {
+        let object = json
+            .as_object()
+            .ok_or_else(|| eyre!("{}: a condition must be an object", path))?;
+
+        let list = |key: &str| -> Result<Vec<Condition>> {
+            object[key]
+                .as_array()
+                .ok_or_else(|| eyre!("{}.{}: expected a list of conditions", path, key))?
+                .iter()
+                .enumerate()
+                .map(|(index, condition)| Condition::parse(condition, &format!("{}.{}[{}]", path, key, index)))
+                .collect()
+        };
+        let mut keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        if let Some(combinator) = keys.iter().find(|key| COMBINATORS.contains(*key)) {
+            if keys.len() > 1 {
+                return Err(eyre!(
+                    "{}: \"{}\" must be the only key of its condition, found {}",
+                    path,
+                    combinator,
+                    keys.join(", ")
+                ));
+            }
+            return match *combinator {
+                "all" => Ok(Condition::All(list("all")?)),
+                "any" => Ok(Condition::Any(list("any")?)),
+                _ => Ok(Condition::Not(Box::new(Condition::parse(&object["not"], &format!("{}.not", path))?))),
+            };
+        }
+        if let Some(key) = keys.iter().find(|key| !COMPARISON.contains(*key)) {
+            return Err(eyre!(
+                "{}: unknown key \"{}\", expected \"all\", \"any\", \"not\" or \"field\", \"op\" and \"value\"",
+                path,
+                key
+            ));
+        }
+
+        let name = object
+            .get("field")
+            .and_then(Json::as_str)
+            .ok_or_else(|| eyre!("{}: expected \"all\", \"any\", \"not\" or a \"field\"", path))?;
+        let (_, field, kind) = FIELDS
+            .iter()
+            .find(|(known, _, _)| *known == name)
+            .ok_or_else(|| {
+                let known: Vec<&str> = FIELDS.iter().map(|(known, _, _)| *known).collect();
+                eyre!("{}: unknown field \"{}\", expected one of {}", path, name, known.join(", "))
+            })?;
+        let op_name = object
+            .get("op")
+            .and_then(Json::as_str)
+            .ok_or_else(|| eyre!("{}: missing \"op\"", path))?;
+        let op = Op::parse(op_name)
+            .ok_or_else(|| eyre!("{}: unknown op \"{}\", expected ==, !=, >, >=, <, <= or matches", path, op_name))?;
+        if !op.applies_to(*kind) {
+            return Err(eyre!("{}: \"{}\" can't be used on \"{}\", which is {}", path, op_name, name, kind));
+        }
+        let json_value = object
+            .get("value")
+            .ok_or_else(|| eyre!("{}: missing \"value\"", path))?;
+        let value = match (kind, json_value) {
+            (Type::Number, Json::Number(number)) => Value::Number(number.as_f64().unwrap_or_default()),
+            (Type::Bool, Json::Bool(boolean)) => Value::Bool(*boolean),
+            (Type::Text, Json::String(text)) => Value::Text(text.clone()),
+            _ => return Err(eyre!("{}: the value of \"{}\" must be {}", path, name, kind)),
+        };
+
+        match (op, value) {
+            (Op::Matches, Value::Text(pattern)) => {
+                let regex = Regex::new(&pattern).wrap_err_with(|| format!("{}: invalid regex", path))?;
+                Ok(Condition::Matches { field: *field, regex })
+            }
+            (op, value) => Ok(Condition::Compare { field: *field, op, value }),
+        }
+    }
+
+    fn matches(&self, launch: &Launch) -> bool {
+        self.eval(&|field: Field| launch.get(field))
+    }
+
+    /// Evaluates the condition on the values given by `get`, `None` being unknown.
+    fn eval(&self, get: &dyn Fn(Field) -> Option<Value>) -> bool {
+        match self {
+            Condition::All(conditions) => conditions.iter().all(|condition| condition.eval(get)),
+            Condition::Any(conditions) => conditions.iter().any(|condition| condition.eval(get)),
+            Condition::Not(condition) => !condition.eval(get),
+            Condition::Matches { field, regex } => match get(*field) {
+                Some(Value::Text(text)) => regex.is_match(&text),
+                _ => false,
+            },
+            Condition::Compare { field, op, value } => match (get(*field), value) {
+                (Some(Value::Number(left)), Value::Number(right)) => match op {
+                    Op::Eq => left == *right,
+                    Op::Ne => left != *right,
+                    Op::Gt => left > *right,
+                    Op::Ge => left >= *right,
+                    Op::Lt => left < *right,
+                    Op::Le => left <= *right,
+                    Op::Matches => false,
+                },
+                (Some(Value::Bool(left)), Value::Bool(right)) => match op {
+                    Op::Eq => left == *right,
+                    Op::Ne => left != *right,
+                    _ => false,
+                },
+                (Some(Value::Text(left)), Value::Text(right)) => match op {
+                    Op::Eq => left.eq_ignore_ascii_case(right),
+                    Op::Ne => !left.eq_ignore_ascii_case(right),
+                    _ => false,
+                },
+                _ => false,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    name: String,
+    when: Json,
+    #[serde(default)]
+    sinks: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFilters {
+    rules: Vec<RawRule>,
+}
+
+pub struct Rule {
+    pub name: String,
+    when: Condition,
+    /// Alert sinks the matching launches go to, all of them when `None`.
+    pub sinks: Option<Vec<String>>,
+}
+
+/// Alert rules, a launch is alerted when any of them matches.
+pub struct Filters {
+    rules: Vec<Rule>,
+}
+
+impl Filters {
+    /// Loads and validates the rules of a file, errors point at the faulty rule.
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).wrap_err_with(|| format!("Can't read filters {}", path))?;
+        let raw: RawFilters =
+            serde_json::from_str(&content).wrap_err_with(|| format!("Invalid filters file {}", path))?;
+        if raw.rules.is_empty() {
+            return Err(eyre!("{}: no rules, every launch would be filtered out", path));
+        }
+        let rules = raw
+            .rules
+            .into_iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                let path = format!("rules[{}] \"{}\"", index, rule.name);
+                let when = Condition::parse(&rule.when, &format!("{}.when", path))?;
+                for sink in rule.sinks.iter().flatten() {
+                    if !SINKS.contains(&sink.as_str()) {
+                        return Err(eyre!("{}.sinks: unknown sink \"{}\", expected one of {}", path, sink, SINKS.join(", ")));
+                    }
+                }
+                Ok(Rule { name: rule.name, when, sinks: rule.sinks })
+            })
+            .collect::<Result<Vec<Rule>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// First rule matching the launch.
+    pub fn matching(&self, launch: &Launch) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.when.matches(launch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn parse_error(condition: Json) -> String {
+        match Condition::parse(&condition, "when") {
+            Ok(_) => panic!("{} was accepted", condition),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn rejects_combined_combinators() {
+        assert_eq!(
+            parse_error(json!({ "all": [], "any": [] })),
+            "when: \"all\" must be the only key of its condition, found all, any"
+        );
+        assert_eq!(
+            parse_error(json!({ "not": { "field": "honeypot", "op": "==", "value": true }, "field": "verified" })),
+            "when: \"not\" must be the only key of its condition, found field, not"
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(parse_error(json!({ "field": "liquidity_usd", "op": ">=", "valeu": 1000 }))
+            .starts_with("when: unknown key \"valeu\""));
+        assert!(parse_error(json!({ "any": [{ "feild": "verified", "op": "==", "value": true }] }))
+            .starts_with("when.any[0]: unknown key \"feild\""));
+        assert!(parse_error(json!({ "al": [] })).starts_with("when: unknown key \"al\""));
+    }
+
+    #[test]
+    fn rejects_invalid_comparisons() {
+        assert!(parse_error(json!({ "field": "buy_taxes", "op": "<", "value": 10 })).starts_with("when: unknown field \"buy_taxes\""));
+        assert_eq!(
+            parse_error(json!({ "field": "verified", "op": ">", "value": true })),
+            "when: \">\" can't be used on \"verified\", which is a boolean"
+        );
+        assert_eq!(
+            parse_error(json!({ "field": "buy_tax", "op": "<", "value": "10" })),
+            "when: the value of \"buy_tax\" must be a number"
+        );
+    }
+
+    #[test]
+    fn not_over_unknown_value_matches() {
+        let condition = json!({ "field": "buy_tax", "op": "<", "value": 10 });
+        let unknown = |_: Field| -> Option<Value> { None };
+        assert!(!Condition::parse(&condition, "when").unwrap().eval(&unknown));
+        assert!(Condition::parse(&json!({ "not": condition.clone() }), "when").unwrap().eval(&unknown));
+
+        let taxed = |_: Field| -> Option<Value> { Some(Value::Number(5.0)) };
+        assert!(!Condition::parse(&json!({ "not": condition }), "when").unwrap().eval(&taxed));
+    }
+}
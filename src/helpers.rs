@@ -12,12 +12,34 @@ pub fn token_amount(input: u128, decimal: u8) -> f64 {
     input as f64 / 10.0_f64.powi(decimal as i32)
 }
 
-pub fn weth_price() -> f64 {
-    3000.0
-}
-
 pub fn to_1e18(input: u64) -> U256 {
     let ether: U256 = U256::exp10(18);
     let parsed: U256 = input.into();
     parsed * ether
 }
+
+pub fn u256_to_float(input: U256) -> f64 {
+    input.to_string().parse::<f64>().unwrap_or(0.0)
+}
+
+/// `token_amount` of any uint256, which may not fit in a u128.
+pub fn u256_amount(input: U256, decimal: u8) -> f64 {
+    u256_to_float(input) / 10.0_f64.powi(decimal as i32)
+}
+
+/// Price of token0 in token1 from a UniswapV3 `sqrtPriceX96`.
+pub fn sqrt_price_x96_to_price(sqrt_price_x96: U256, decimals0: u8, decimals1: u8) -> f64 {
+    let sqrt_price = u256_to_float(sqrt_price_x96) / 2.0_f64.powi(96);
+    sqrt_price * sqrt_price * 10.0_f64.powi(decimals0 as i32 - decimals1 as i32)
+}
+
+/// Price of token0 in token1 at a UniswapV3 tick.
+pub fn tick_to_price(tick: i32, decimals0: u8, decimals1: u8) -> f64 {
+    1.0001_f64.powi(tick) * 10.0_f64.powi(decimals0 as i32 - decimals1 as i32)
+}
+
+/// Address of the UniswapV2 like pair of two sorted tokens, created or not.
+pub fn pair_address(factory: Address, token0: Address, token1: Address, init_code_hash: H256) -> Address {
+    let salt = ethers::utils::keccak256([token0.as_bytes(), token1.as_bytes()].concat());
+    ethers::utils::get_create2_address_from_hash(factory, salt, init_code_hash)
+}
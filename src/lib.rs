@@ -4,21 +4,69 @@ pub mod mempool;
 pub mod address_book;
 pub mod dex;
 pub mod pair;
+pub mod pool;
 pub mod token;
 pub mod alert;
 pub mod contracts;
+pub mod backfill;
+pub mod store;
+pub mod subscription;
+pub mod oracle;
+pub mod quote;
+pub mod simulation;
+pub mod risk;
+pub mod lp;
+pub mod wallet;
+pub mod filter;
+pub mod events;
+pub mod output;
 
-use contracts::get_contracts_data;
-use helpers::address;
+use contracts::{get_contracts_data, ChainData, ContractAddresses};
 
-use crate::dex::Dex;
+use crate::alert::template::Templates;
+use crate::block_scanner::BlockTracker;
+use crate::alert::AlertDispatcher;
+use crate::dex::{Dex, Settings, Watchers};
+use crate::events::EventBus;
+use crate::filter::Filters;
+use crate::mempool::MempoolWatcher;
+use crate::pair::RugSettings;
+use crate::oracle::PriceOracle;
+use crate::output::{EventWriter, OutputFormat};
+use crate::quote::QuoteRegistry;
+use crate::store::Store;
+use crate::subscription::LogSubscriber;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use ethers::prelude::*;
+use eyre::{Result, WrapErr};
 use tokio::signal::ctrl_c;
-use clap::Parser;
+use tokio::task::JoinSet;
+use clap::{Parser, Subcommand};
 use colored::*;
 
+/// Set when stdout carries the JSON events, the console lines go to stderr instead.
+static CONSOLE_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+#[doc(hidden)]
+pub fn console_to_stderr() -> bool {
+    CONSOLE_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// `println!` for the human readable lines, see `CONSOLE_TO_STDERR`.
+#[macro_export]
+macro_rules! console_println {
+    ($($arg: tt)*) => {
+        if $crate::console_to_stderr() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! timestamp_print {
     ($color: expr, $large: expr, $message: expr) => {
@@ -27,7 +75,7 @@ macro_rules! timestamp_print {
         } else {
             $message.clone()
         };
-        println!(
+        $crate::console_println!(
             "{} {} {}",
             chrono::Local::now()
                 .format("[%Y-%m-%d]")
@@ -40,8 +88,14 @@ macro_rules! timestamp_print {
             formatted_message.color($color)
         );
     };
+    ($color: expr, $large: expr, $chain: expr, $message: expr) => {
+        $crate::timestamp_print!($color, $large, format!("[{}] {}", $chain, $message));
+    };
 }
 
+/// Delay before a dex stream that stopped is started again.
+const RESTART_DELAY: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone, Copy)]
 pub enum Chain {
     Ethereum,
@@ -70,128 +124,517 @@ impl std::str::FromStr for Chain {
     }
 }
 
+impl Chain {
+    pub fn all() -> [Chain; 7] {
+        [
+            Chain::Ethereum,
+            Chain::Arbitrum,
+            Chain::Polygon,
+            Chain::Bsc,
+            Chain::Fantom,
+            Chain::Avalanche,
+            Chain::Cronos,
+        ]
+    }
+
+    /// Key of the chain in the env variables and the contracts JSON.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Chain::Ethereum => "ETHEREUM",
+            Chain::Arbitrum => "ARBITRUM",
+            Chain::Polygon => "POLYGON",
+            Chain::Bsc => "BSC",
+            Chain::Fantom => "FANTOM",
+            Chain::Avalanche => "AVALANCHE",
+            Chain::Cronos => "CRONOS",
+        }
+    }
+}
+
+/// A `--chain` value: a single chain or `all` of them.
+#[derive(Debug, Clone, Copy)]
+enum ChainArg {
+    All,
+    One(Chain),
+}
+
+impl std::str::FromStr for ChainArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(ChainArg::All),
+            _ => s.parse::<Chain>().map(ChainArg::One),
+        }
+    }
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-   /// Chain that you want to track
-   #[arg(short, long, default_value = "ethereum")]
-   chain: Chain,
+   /// Chains that you want to track, comma separated or `all`
+   #[arg(short, long, default_value = "ethereum", value_delimiter = ',')]
+   chain: Vec<ChainArg>,
    /// Monitor block creation
    #[arg(short, long, default_value = "false")]
    block: bool,
    /// JSON file with addresses to monitor
    #[arg(short, long, default_value = "src/json/dex_contracts.json")]
    json: String,
-   /// Discord webhook alert
+   /// Send the alerts to the configured sinks (Discord, Telegram, webhook, Slack)
    #[arg(short, long, default_value = "false")]
    alert: bool,
    /// Token contract verification
    /// This will check if the token contract is verified on the explorer
     #[arg(short, long, default_value = "false")]
     verify: bool,
+    /// SQLite database storing the pairs and liquidity events
+    /// Requires the `sqlite` feature
+    #[arg(long)]
+    db: Option<String>,
+    /// Share of the liquidity (in %) that has to be removed to raise a "liquidity pulled" alert
+    #[arg(long, default_value = "50")]
+    rug_threshold: f64,
+    /// Minutes after the launch during which liquidity removals are monitored, 0 to disable
+    #[arg(long, default_value = "60")]
+    rug_window: u64,
+    /// Minutes after the launch during which the LP token transfers (burns, locks) are followed, 0 to disable
+    #[arg(long, default_value = "60")]
+    lp_window: u64,
+    /// Messages per minute sent to each alert sink, the others wait in the delivery queue
+    #[arg(long, default_value = "30")]
+    alert_rate: u32,
+    /// JSON file with the rules a launch must match to be alerted
+    #[arg(long)]
+    filters: Option<String>,
+    /// Blocks mined on top of an event before it is processed, `0` for none
+    #[arg(long, default_value = "0")]
+    confirmations: u64,
+    /// JSON file with the messages sent by the chat alert sinks
+    #[arg(long)]
+    templates: Option<String>,
+    /// Alert the liquidity additions still pending in the mempool
+    #[arg(long, default_value = "false")]
+    mempool: bool,
+    /// Format of the events, `json` writes one JSON object per event (NDJSON)
+    #[arg(long, value_enum, default_value = "text")]
+    output: OutputFormat,
+    /// File the JSON events are appended to, instead of stdout
+    #[arg(long)]
+    output_file: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone, Copy)]
+enum Command {
+    /// Replay the pairs created in a past block range instead of streaming the new ones
+    Backfill {
+        /// First block, defaults to the `start_block` of each dex
+        #[arg(long)]
+        from_block: Option<u64>,
+        /// Last block, defaults to the latest one
+        #[arg(long)]
+        to_block: Option<u64>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub struct Config {
     #[allow(dead_code)]
     pub http: Arc<Provider<Http>>,
-    #[allow(dead_code)]
-    pub wss: Arc<Provider<Ws>>,
+    /// Reconnecting log subscriptions, shared by every dex of the chain.
+    pub logs: LogSubscriber,
+    /// New block headers of the chain.
+    pub blocks: BlockTracker,
+    /// Blocks mined on top of a log before it is processed.
+    pub confirmations: u64,
+    /// Pairs and pools watched on the chain, shared by the restarts of the dex streams.
+    pub watched: Arc<Watchers>,
+    /// Where the tracker events of the chain are published.
+    pub events: EventBus,
+    /// Pending liquidity additions, `None` unless `--mempool` is set.
+    pub mempool: Option<MempoolWatcher>,
+    /// Native token USD price, `None` on chains without a reference pool.
+    pub oracle: Option<Arc<PriceOracle>>,
+    /// Quote assets used to tell the new token of a pair apart.
+    pub quotes: Arc<QuoteRegistry>,
+    /// Alert sinks of the chain.
+    pub alerts: Arc<AlertDispatcher>,
 }
 
 impl Config {
-    pub async fn new(chain: &str) -> Self {
-        
-        
-        let network = std::env::var(format!("NETWORK_RPC_{}", chain)).expect("missing NETWORK_RPC");
-        let provider: Provider<Http> = Provider::<Http>::try_from(network).unwrap();
+    pub async fn new(chain: &str, settings: &Settings, store: Option<Arc<Store>>) -> Result<Self> {
+        let rpc_env = format!("NETWORK_RPC_{}", chain);
+        let network = std::env::var(&rpc_env).wrap_err_with(|| format!("missing {}", rpc_env))?;
+        let provider: Provider<Http> = Provider::<Http>::try_from(network)?;
         let middleware = Arc::new(provider);
 
-        let ws_network = std::env::var(format!("NETWORK_WSS_{}", chain)).expect("missing NETWORK_WSS");
-        let ws_provider: Provider<Ws> = Provider::<Ws>::connect(ws_network).await.unwrap();
+        let wss_env = format!("NETWORK_WSS_{}", chain);
+        let ws_network = std::env::var(&wss_env).wrap_err_with(|| format!("missing {}", wss_env))?;
+        let blocks = BlockTracker::new(chain.to_string(), ws_network.clone(), middleware.clone(), settings.events.clone());
+        let confirmations = match std::env::var(format!("CONFIRMATIONS_{}", chain)) {
+            Ok(confirmations) => confirmations
+                .parse()
+                .wrap_err_with(|| format!("CONFIRMATIONS_{} must be a number of blocks", chain))?,
+            Err(_) => settings.confirmations,
+        };
+        let mempool = settings.mempool.then(|| MempoolWatcher::new(chain.to_string(), ws_network.clone()));
+        let logs = LogSubscriber::new(chain.to_string(), ws_network, middleware.clone(), &blocks, confirmations);
+        let quotes = Arc::new(QuoteRegistry::for_chain(chain)?);
+        let oracle = PriceOracle::for_chain(chain, middleware.clone(), quotes.clone())?.map(Arc::new);
+        let alerts = match settings.alert {
+            true => AlertDispatcher::for_chain(chain, settings.alert_rate, settings.templates.clone(), store)?,
+            false => AlertDispatcher::disabled(chain),
+        };
         
-        Self {
+        Ok(Self {
             http: middleware,
-            wss: Arc::new(ws_provider),
-        }
+            logs,
+            blocks,
+            confirmations,
+            events: settings.events.clone(),
+            watched: Arc::new(Watchers::default()),
+            mempool,
+            oracle,
+            quotes,
+            alerts: Arc::new(alerts),
+        })
     }
 
-    pub async fn create_dex(&self, factory: Address, router: Address, chain: String, alert: bool, verify: bool) -> Dex {
+    pub async fn create_dex(&self, name: String, contracts: &ContractAddresses, chain: String, settings: Settings, store: Option<Arc<Store>>) -> Dex {
         Dex::new(
-            self.http.clone(), 
-            factory, 
-            router, 
+            self, 
+            name, 
+            contracts, 
             chain, 
-            alert, 
-            verify
+            settings,
+            store
         )
     }
 }
 
-fn conf_arg() -> (String, bool, String, bool, bool) {
+/// Command line arguments, with the chains resolved to their keys.
+struct Options {
+    chains: Vec<String>,
+    /// Chains named on the command line, the others only come from `all`.
+    named: Vec<String>,
+    block_monitoring: bool,
+    path: String,
+    alert: bool,
+    verify: bool,
+    db: Option<String>,
+    rug: RugSettings,
+    lp_window: Duration,
+    alert_rate: u32,
+    filters: Option<String>,
+    templates: Option<String>,
+    confirmations: u64,
+    mempool: bool,
+    output: OutputFormat,
+    output_file: Option<String>,
+    command: Option<Command>,
+}
+
+fn conf_arg() -> Options {
     let args = Args::parse();
-    let key;
-    match args.chain {
-        Chain::Ethereum => {
-            key = "ETHEREUM"
-        }
-        Chain::Arbitrum => {
-            key = "ARBITRUM"
-        }
-        Chain::Polygon => {
-            key = "POLYGON"
-        }
-        Chain::Bsc => {
-            key = "BSC"
+    let mut keys: Vec<String> = Vec::new();
+    let mut named: Vec<String> = Vec::new();
+    for chain in args.chain {
+        let chains = match chain {
+            ChainArg::All => Chain::all().to_vec(),
+            ChainArg::One(chain) => {
+                named.push(chain.key().to_string());
+                vec![chain]
+            }
+        };
+        for chain in chains {
+            if !keys.iter().any(|key| key == chain.key()) {
+                keys.push(chain.key().to_string());
+            }
         }
-        Chain::Fantom => {
-            key = "FANTOM"
+    }
+    if !(args.rug_threshold > 0.0 && args.rug_threshold <= 100.0) {
+        timestamp_print!(Color::Red, Some(true), format!("--rug-threshold must be above 0 and at most 100, got {}", args.rug_threshold));
+        std::process::exit(1);
+    }
+    let rug = RugSettings {
+        threshold: args.rug_threshold / 100.0,
+        window: Duration::from_secs(args.rug_window * 60),
+    };
+    Options {
+        chains: keys,
+        named,
+        block_monitoring: args.block,
+        path: args.json,
+        alert: args.alert,
+        verify: args.verify,
+        db: args.db,
+        rug,
+        lp_window: Duration::from_secs(args.lp_window * 60),
+        alert_rate: args.alert_rate,
+        filters: args.filters,
+        templates: args.templates,
+        confirmations: args.confirmations,
+        mempool: args.mempool,
+        output: args.output,
+        output_file: args.output_file,
+        command: args.command,
+    }
+}
+
+/// Keeps a dex stream running, it is started again whenever it stops or panics.
+async fn supervise_dex(config: Config, name: String, contracts: ContractAddresses, chain: String, settings: Settings, store: Option<Arc<Store>>) {
+    loop {
+        let dex = config.create_dex(name.clone(), &contracts, chain.clone(), settings.clone(), store.clone()).await;
+        let (logs, http) = (config.logs.clone(), config.http.clone());
+        let reason = match tokio::spawn(dex.stream(logs, http)).await {
+            Ok(Ok(())) => "stream ended".to_string(),
+            Ok(Err(err)) => format!("stream failed: {}", err),
+            Err(err) => format!("task panicked: {}", err),
+        };
+        timestamp_print!(
+            Color::Red,
+            Some(false),
+            chain,
+            format!("[{}] {}, restarting in {}s", name, reason, RESTART_DELAY.as_secs())
+        );
+        tokio::time::sleep(RESTART_DELAY).await;
+    }
+}
+
+/// Runs every dex of a chain on a single `Config`.
+async fn run_chain(config: Config, chain: String, chain_data: Option<ChainData>, block_monitoring: bool, settings: Settings, store: Option<Arc<Store>>) {
+    if settings.alert {
+        if config.alerts.is_empty() {
+            timestamp_print!(Color::Yellow, Some(false), chain, format!("[ALERT] No alert sink configured, see .env.example"));
+        } else {
+            timestamp_print!(Color::Green, Some(false), chain, format!("[ALERT] Sending alerts to {}", config.alerts.names().join(", ")));
         }
-        Chain::Avalanche => {
-            key = "AVALANCHE"
+    }
+
+    let mut tasks = JoinSet::new();
+    if block_monitoring {
+        timestamp_print!(Color::Green, Some(true), chain, format!("Starting block monitor"));
+        tasks.spawn(block_scanner::loop_blocks(config.events.subscribe(), chain.clone()));
+        // The dex streams may never subscribe to the headers, the banner needs the head anyway.
+        config.blocks.start();
+    }
+
+    match chain_data {
+        Some(chain_data) => {
+            for (name, addresses) in chain_data.addresses {
+                tasks.spawn(supervise_dex(
+                    config.clone(),
+                    name, 
+                    addresses, 
+                    chain.clone(),
+                    settings.clone(),
+                    store.clone()
+                ));
+            }
         }
-        Chain::Cronos => {
-            key = "CRONOS"
+        None => {
+            timestamp_print!(Color::Red, Some(false), chain, format!("Chain not supported"));
         }
     }
-    (key.to_string(), args.block, args.json, args.alert, args.verify)
+
+    while tasks.join_next().await.is_some() {}
 }
 
-async fn config_task(dex_router: H160, dex_factory: H160, chain: String, alert: bool, verify: bool) {
-    let config = Config::new(chain.as_str()).await;
-    tokio::spawn(async move {
-        let dex = config.create_dex(dex_factory, dex_router, chain.clone(), alert, verify).await;
-        dex.stream_pairs_created(config.wss, config.http).await.unwrap();
-    });
+/// False for a chain that only comes from `all` and has no RPC or WSS URL, it is
+/// skipped instead of stopping the bot.
+fn is_selected(chain: &str, named: &[String]) -> bool {
+    if named.iter().any(|key| key == chain) {
+        return true;
+    }
+    let missing: Vec<String> = ["NETWORK_RPC", "NETWORK_WSS"]
+        .iter()
+        .map(|prefix| format!("{}_{}", prefix, chain))
+        .filter(|env| !std::env::var(env).is_ok_and(|url| !url.is_empty()))
+        .collect();
+    if missing.is_empty() {
+        return true;
+    }
+    timestamp_print!(Color::Yellow, Some(false), chain, format!("Skipped, {} not set", missing.join(" and ")));
+    false
+}
+
+/// A chain whose environment or connection is invalid stops the bot, rather than
+/// running without it.
+fn exit_on_config_error(chain: &str, err: eyre::Report) -> ! {
+    timestamp_print!(Color::Red, Some(true), chain, format!("{:#}", err));
+    std::process::exit(1);
+}
+
+/// Backfills every dex of a chain over a block range, alerts are never sent for past pairs.
+#[allow(clippy::too_many_arguments)]
+async fn backfill_chain(chain: String, chain_data: ChainData, from_block: Option<u64>, to_block: Option<u64>, verify: bool, confirmations: u64, store: Option<Arc<Store>>, events: EventBus) {
+    // Past pairs are never alerted nor watched for removals, the windows are irrelevant.
+    let settings = Settings {
+        alert: false,
+        verify,
+        rug: RugSettings { threshold: 1.0, window: Duration::ZERO },
+        lp_window: Duration::ZERO,
+        alert_rate: 0,
+        filters: None,
+        templates: None,
+        confirmations,
+        mempool: false,
+        events,
+    };
+    let config = match Config::new(chain.as_str(), &settings, store.clone()).await {
+        Ok(config) => config,
+        Err(err) => exit_on_config_error(&chain, err),
+    };
+    // By default, up to the last block with enough confirmations.
+    let to_block = match to_block {
+        Some(block) => block,
+        None => match config.http.get_block_number().await {
+            Ok(block) => block.as_u64().saturating_sub(config.confirmations),
+            Err(err) => {
+                timestamp_print!(Color::Red, Some(true), chain, format!("{}", err));
+                return;
+            }
+        },
+    };
+
+    let mut tasks = JoinSet::new();
+    for (name, contracts) in chain_data.addresses {
+        let from_block = match from_block.or(contracts.start_block) {
+            Some(block) => block,
+            None => {
+                timestamp_print!(Color::Red, Some(false), chain, format!("[BACKFILL][{}] No --from-block and no start_block in the JSON, skipped", name));
+                continue;
+            }
+        };
+        let dex = Arc::new(config.create_dex(name.clone(), &contracts, chain.clone(), settings.clone(), store.clone()).await);
+        let http = config.http.clone();
+        tasks.spawn(async move { (name, dex.backfill(http, from_block, to_block).await) });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok((name, Ok(found))) => {
+                timestamp_print!(Color::Green, Some(true), chain, format!("[BACKFILL][{}] Done, {} pairs found", name, found));
+            }
+            Ok((name, Err(err))) => {
+                timestamp_print!(Color::Red, Some(true), chain, format!("[BACKFILL][{}] {}", name, err));
+            }
+            Err(err) => {
+                timestamp_print!(Color::Red, Some(true), chain, format!("[BACKFILL] {}", err));
+            }
+        }
+    }
 }
 
 pub async fn run() {    
-    let (chain, block_monitoring, path, alert, verify) = conf_arg();
-    let chain_block = chain.clone();
+    let Options { chains, named, block_monitoring, path, alert, verify, db, rug, lp_window, alert_rate, filters, templates, confirmations, mempool, output, output_file, command } = conf_arg();
+    if output == OutputFormat::Json && output_file.is_none() {
+        CONSOLE_TO_STDERR.store(true, Ordering::Relaxed);
+    }
 
-    let data = get_contracts_data(path);
+    if verify && std::env::var("ETHERSCAN_API_KEY").is_err() {
+        timestamp_print!(Color::Red, Some(true), format!("--verify needs ETHERSCAN_API_KEY, see .env.example"));
+        std::process::exit(1);
+    }
 
-    if block_monitoring {
-        timestamp_print!(Color::Green, Some(true), format!("Starting block monitor"));
-        let config = Config::new(chain_block.as_str()).await;
-        tokio::spawn(async move {
-            block_scanner::loop_blocks(config.http).await;
-        });
-    }
-
-    if let Some(chain_data) = data.get(chain.as_str()) {
-        for (_, addresses) in &chain_data.addresses {
-            config_task(
-                address(addresses.router.as_str()), 
-                address(addresses.factory.as_str()), 
-                chain.clone(),
-                alert,
-                verify
-            ).await;
+    let mut data = match get_contracts_data(path) {
+        Ok(data) => data,
+        Err(err) => {
+            timestamp_print!(Color::Red, Some(true), format!("{}", err));
+            std::process::exit(1);
         }
-    } else {
-        println!("Chain not supported");
-    }    
+    };
+
+    let store = match db {
+        Some(path) => match Store::open(path.as_str()) {
+            Ok(store) => Some(Arc::new(store)),
+            Err(err) => {
+                timestamp_print!(Color::Red, Some(true), format!("{:#}", err));
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let filters = match filters {
+        Some(path) => match Filters::load(path.as_str()) {
+            Ok(filters) => {
+                timestamp_print!(Color::Green, Some(false), format!("[FILTER] {} rules loaded from {}", filters.len(), path));
+                Some(Arc::new(filters))
+            }
+            Err(err) => {
+                timestamp_print!(Color::Red, Some(true), format!("{:#}", err));
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let templates = match templates {
+        Some(path) => match Templates::load(path.as_str()) {
+            Ok(templates) => {
+                timestamp_print!(Color::Green, Some(false), format!("[TEMPLATE] {} messages loaded from {}", templates.len(), path));
+                Some(Arc::new(templates))
+            }
+            Err(err) => {
+                timestamp_print!(Color::Red, Some(true), format!("{:#}", err));
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let events = EventBus::new();
+    let writer = match output {
+        OutputFormat::Json => match EventWriter::start(events.subscribe(), output_file.as_deref()) {
+            Ok(writer) => Some(writer),
+            Err(err) => {
+                timestamp_print!(Color::Red, Some(true), format!("{:#}", err));
+                std::process::exit(1);
+            }
+        },
+        OutputFormat::Text => None,
+    };
+
+    if let Some(Command::Backfill { from_block, to_block }) = command {
+        for chain in chains {
+            if !is_selected(&chain, &named) {
+                continue;
+            }
+            if let Some(chain_data) = data.remove(chain.as_str()) {
+                backfill_chain(chain, chain_data, from_block, to_block, verify, confirmations, store.clone(), events.clone()).await;
+            } else {
+                timestamp_print!(Color::Red, Some(false), chain, format!("Chain not supported"));
+            }
+        }
+        if let Some(writer) = writer {
+            writer.finish().await;
+        }
+        return;
+    }
+
+    let settings = Settings { alert, verify, rug, lp_window, alert_rate, filters, templates, confirmations, mempool, events };
+    // Every chain is configured before any starts, a chain that can't be is fatal.
+    let mut configured = Vec::new();
+    for chain in chains {
+        if !is_selected(&chain, &named) {
+            continue;
+        }
+        let chain_data = data.remove(chain.as_str());
+        if chain_data.is_none() && !block_monitoring {
+            timestamp_print!(Color::Red, Some(false), chain, format!("Chain not supported"));
+            continue;
+        }
+        match Config::new(chain.as_str(), &settings, store.clone()).await {
+            Ok(config) => configured.push((config, chain, chain_data)),
+            Err(err) => exit_on_config_error(&chain, err),
+        }
+    }
+    for (config, chain, chain_data) in configured {
+        tokio::spawn(run_chain(config, chain, chain_data, block_monitoring, settings.clone(), store.clone()));
+    }
 
     let (tx, rx) = tokio::sync::oneshot::channel();
     tokio::spawn(async move {
@@ -200,4 +643,7 @@ pub async fn run() {
     });
 
     let _ = rx.await;
+    if let Some(writer) = writer {
+        writer.finish().await;
+    }
 }
\ No newline at end of file
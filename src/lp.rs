@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use chrono::{TimeZone, Utc};
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::address_book::{known_locker, LockerInfo, LockerKind, LpPair, KNOWN_LOCKERS};
+use crate::helpers::u256_to_float;
+
+abigen!(
+    UnicryptLocker,
+    r#"[
+        function getNumLocksForToken(address lpToken) external view returns (uint256)
+        function tokenLocks(address lpToken, uint256 index) external view returns (uint256 lockDate, uint256 amount, uint256 initialAmount, uint256 unlockDate, uint256 lockID, address owner)
+    ]"#
+);
+
+const DEAD: &str = "0x000000000000000000000000000000000000dead";
+
+/// Where a LP transfer went.
+#[derive(Clone, Copy)]
+pub enum LpHolder {
+    /// Zero or dead address.
+    Burned,
+    Locker(&'static LockerInfo),
+    /// Wallet that received the first LP minted.
+    Deployer,
+    Other,
+}
+
+impl fmt::Display for LpHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LpHolder::Burned => write!(f, "burned"),
+            LpHolder::Locker(locker) => write!(f, "locked in {}", locker.name),
+            LpHolder::Deployer => write!(f, "sent to the deployer"),
+            LpHolder::Other => write!(f, "sent to another wallet"),
+        }
+    }
+}
+
+/// Follows the LP token `Transfer` events of a pair to tell how much of the
+/// supply is burned, locked or still held by the deployer.
+pub struct LpTracker {
+    chain: String,
+    pair: Address,
+    deployer: Option<Address>,
+    balances: HashMap<Address, U256>,
+}
+
+impl LpTracker {
+    pub fn new(chain: String, pair: Address) -> Self {
+        Self {
+            chain,
+            pair,
+            deployer: None,
+            balances: HashMap::new(),
+        }
+    }
+
+    pub fn classify(&self, holder: Address) -> LpHolder {
+        if holder == Address::zero() || Some(holder) == DEAD.parse().ok() {
+            LpHolder::Burned
+        } else if let Some(locker) = known_locker(&self.chain, holder) {
+            LpHolder::Locker(locker)
+        } else if Some(holder) == self.deployer {
+            LpHolder::Deployer
+        } else {
+            LpHolder::Other
+        }
+    }
+
+    /// Applies a LP transfer. Returns where it went when it is worth reporting,
+    /// i.e. the LP was burned, locked or moved by the deployer.
+    pub fn on_transfer(&mut self, from: Address, to: Address, value: U256) -> Option<LpHolder> {
+        // The first mint locks MINIMUM_LIQUIDITY on the zero address, and a
+        // removeLiquidity burns the LP sent back to the pair: neither is a burn.
+        if from == Address::zero() && to == Address::zero() {
+            return None;
+        }
+        if from == Address::zero() && self.deployer.is_none() {
+            self.deployer = Some(to);
+        }
+        if from != Address::zero() {
+            let balance = self.balances.entry(from).or_default();
+            *balance = balance.saturating_sub(value);
+        }
+        if from == self.pair && to == Address::zero() {
+            return None;
+        }
+        *self.balances.entry(to).or_default() += value;
+
+        let holder = self.classify(to);
+        match holder {
+            LpHolder::Burned | LpHolder::Locker(_) => Some(holder),
+            _ if from != Address::zero() && Some(from) == self.deployer => Some(holder),
+            _ => None,
+        }
+    }
+
+    /// Share of the LP supply held by each kind of holder.
+    pub fn distribution(&self) -> LpDistribution {
+        let mut distribution = LpDistribution::default();
+        let total = self
+            .balances
+            .iter()
+            .filter(|(holder, _)| **holder != self.pair)
+            .fold(U256::zero(), |total, (_, balance)| total + balance);
+        if total.is_zero() {
+            return distribution;
+        }
+        for (holder, balance) in &self.balances {
+            if *holder == self.pair || balance.is_zero() {
+                continue;
+            }
+            let share = u256_to_float(*balance) / u256_to_float(total);
+            match self.classify(*holder) {
+                LpHolder::Burned => distribution.burned += share,
+                LpHolder::Locker(_) => distribution.locked += share,
+                LpHolder::Deployer => distribution.deployer += share,
+                LpHolder::Other => distribution.other += share,
+            }
+        }
+        distribution
+    }
+}
+
+/// Shares (0 to 1) of the LP supply.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct LpDistribution {
+    pub burned: f64,
+    pub locked: f64,
+    pub deployer: f64,
+    pub other: f64,
+}
+
+impl fmt::Display for LpDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Burned {:.1}% | Locked {:.1}% | Deployer {:.1}% | Other {:.1}%",
+            self.burned * 100.0,
+            self.locked * 100.0,
+            self.deployer * 100.0,
+            self.other * 100.0
+        )
+    }
+}
+
+/// Current LP distribution of a pair, read from the balances of the burn addresses,
+/// the known lockers of the chain and the deployer instead of the transfer history.
+pub async fn snapshot(
+    http: Arc<Provider<Http>>,
+    chain: &str,
+    pair: Address,
+    deployer: Option<Address>,
+) -> Option<LpDistribution> {
+    let contract = LpPair::new(pair, http);
+    let total = contract.total_supply().call().await.ok()?;
+    if total.is_zero() {
+        return None;
+    }
+    let share = |balance: U256| u256_to_float(balance) / u256_to_float(total);
+    let balance = |holder: Address| {
+        let call = contract.balance_of(holder);
+        async move { call.call().await.unwrap_or_default() }
+    };
+
+    let mut distribution = LpDistribution::default();
+    for burn in [Address::zero(), DEAD.parse().ok()?] {
+        distribution.burned += share(balance(burn).await);
+    }
+    for locker in KNOWN_LOCKERS.iter().filter(|locker| locker.chain == chain) {
+        if let Ok(address) = locker.address.parse() {
+            distribution.locked += share(balance(address).await);
+        }
+    }
+    if let Some(deployer) = deployer {
+        distribution.deployer = share(balance(deployer).await);
+    }
+    distribution.other = (1.0 - distribution.burned - distribution.locked - distribution.deployer).max(0.0);
+    Some(distribution)
+}
+
+/// Unlock date of the last lock of `lp_token` on the locker, when the locker exposes it.
+pub async fn unlock_date(http: Arc<Provider<Http>>, locker: &LockerInfo, lp_token: Address) -> Option<String> {
+    match locker.kind {
+        LockerKind::Unicrypt => {
+            let contract = UnicryptLocker::new(locker.address.parse::<Address>().ok()?, http);
+            let count = contract.get_num_locks_for_token(lp_token).call().await.ok()?;
+            if count.is_zero() {
+                return None;
+            }
+            let (_, _, _, unlock_date, _, _) = contract
+                .token_locks(lp_token, count - 1)
+                .call()
+                .await
+                .ok()?;
+            let date = Utc.timestamp_opt(unlock_date.low_u64() as i64, 0).single()?;
+            Some(date.format("%Y-%m-%d %H:%M UTC").to_string())
+        }
+        LockerKind::TeamFinance | LockerKind::PinkLock => None,
+    }
+}
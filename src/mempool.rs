@@ -1,26 +1,145 @@
-use std::sync::Arc;
+//! Pending transactions adding liquidity through a UniswapV2 like router, seen
+//! before they are mined.
+
+use std::time::Duration;
 
 use ethers::{
     abi::AbiDecode,
-    providers::{Middleware, Provider, StreamExt, TransactionStream, Ws}
+    prelude::*,
+    providers::TransactionStream,
 };
+use eyre::Result;
+use tokio::sync::broadcast;
 
 use crate::address_book::UniV2RouterCalls;
 use crate::timestamp_print;
 
 use colored::*;
 
-pub async fn loop_mempool(ws_provider: Arc<Provider<Ws>>) {
-    let tx_hash_stream = ws_provider.subscribe_pending_txs().await.unwrap();
-    let mut tx_stream = TransactionStream::new(&ws_provider, tx_hash_stream, 256);
-
-    timestamp_print!(Color::White, Some(false), format!("---------- MONITORING MEMPOOL ----------"));
-    while let Some(maybe_tx) = tx_stream.next().await {
-        if let Ok(tx) = maybe_tx {
-            if let Ok(decoded) = UniV2RouterCalls::decode(&tx.input) {
-                timestamp_print!(Color::BrightBlue, Some(false), format!("Transaction: {:#?}", tx));
-                timestamp_print!(Color::BrightBlue, Some(false), format!("Router Call: {:#?}", decoded));
-            }
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Pending additions kept for a receiver that falls behind.
+const CAPACITY: usize = 256;
+/// Transactions fetched at once when the node only sends the pending hashes.
+const FETCH_CONCURRENCY: usize = 256;
+
+/// A pending `addLiquidity` or `addLiquidityETH`, as sent to the router.
+#[derive(Clone, Debug)]
+pub struct PendingLiquidity {
+    pub hash: H256,
+    pub router: Address,
+    pub sender: Address,
+    pub token_a: Address,
+    pub amount_a: U256,
+    /// `None` for the native token of `addLiquidityETH`.
+    pub token_b: Option<Address>,
+    pub amount_b: U256,
+    /// Timestamp after which the router reverts the call.
+    pub deadline: U256,
+    /// Max fee per gas, or gas price of a legacy transaction.
+    pub gas_price: Option<U256>,
+}
+
+impl PendingLiquidity {
+    /// `None` when the transaction is not a liquidity addition.
+    fn decode(tx: &Transaction) -> Option<Self> {
+        let router = tx.to?;
+        let (token_a, amount_a, token_b, amount_b, deadline) = match UniV2RouterCalls::decode(&tx.input).ok()? {
+            UniV2RouterCalls::AddLiquidity(call) => (
+                call.token_a,
+                call.amount_a_desired,
+                Some(call.token_b),
+                call.amount_b_desired,
+                call.deadline,
+            ),
+            UniV2RouterCalls::AddLiquidityETH(call) => (call.token, call.amount_token_desired, None, tx.value, call.deadline),
+            _ => return None,
+        };
+        Some(Self {
+            hash: tx.hash,
+            router,
+            sender: tx.from,
+            token_a,
+            amount_a,
+            token_b,
+            amount_b,
+            deadline,
+            gas_price: tx.max_fee_per_gas.or(tx.gas_price),
+        })
+    }
+}
+
+/// Pending liquidity additions of a chain, from a `newPendingTransactions`
+/// subscription. Full transactions are asked first, nodes that only send hashes
+/// get each transaction fetched. The subscription reconnects with an exponential
+/// backoff, what is pending meanwhile is missed.
+#[derive(Clone, Debug)]
+pub struct MempoolWatcher {
+    pending: broadcast::Sender<PendingLiquidity>,
+}
+
+impl MempoolWatcher {
+    pub fn new(chain: String, ws_url: String) -> Self {
+        let (pending, _) = broadcast::channel(CAPACITY);
+        tokio::spawn(watch(chain, ws_url, pending.clone()));
+        Self { pending }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PendingLiquidity> {
+        self.pending.subscribe()
+    }
+}
+
+async fn watch(chain: String, ws_url: String, pending: broadcast::Sender<PendingLiquidity>) {
+    let mut backoff = MIN_BACKOFF;
+    loop {
+        let err = match follow(&chain, &ws_url, &pending, &mut backoff).await {
+            Ok(()) => eyre::eyre!("subscription ended"),
+            Err(err) => err,
+        };
+        timestamp_print!(
+            Color::Red,
+            Some(false),
+            chain,
+            format!("[MEMPOOL] Pending transactions lost: {}, reconnecting in {}s", err, backoff.as_secs())
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Broadcasts the liquidity additions until the subscription stops.
+async fn follow(chain: &str, ws_url: &str, pending: &broadcast::Sender<PendingLiquidity>, backoff: &mut Duration) -> Result<()> {
+    let ws = Provider::<Ws>::connect(ws_url).await?;
+    let mut send = |tx: &Transaction| {
+        *backoff = MIN_BACKOFF;
+        if let Some(liquidity) = PendingLiquidity::decode(tx) {
+            // No receiver is not an error, the mempool is still followed.
+            let _ = pending.send(liquidity);
+        }
+    };
+
+    if let Ok(mut stream) = ws.subscribe_full_pending_txs().await {
+        timestamp_print!(Color::Green, Some(false), chain, format!("[MEMPOOL] Watching pending transactions"));
+        while let Some(tx) = stream.next().await {
+            send(&tx);
+        }
+        return Ok(());
+    }
+
+    let hashes = ws.subscribe_pending_txs().await?;
+    timestamp_print!(
+        Color::Green,
+        Some(false),
+        chain,
+        format!("[MEMPOOL] Watching pending transactions, fetched one by one")
+    );
+    let mut stream = TransactionStream::new(&ws, hashes, FETCH_CONCURRENCY);
+    while let Some(tx) = stream.next().await {
+        // Mined or dropped before it could be fetched.
+        if let Ok(tx) = tx {
+            send(&tx);
         }
     }
-}
\ No newline at end of file
+    Ok(())
+}
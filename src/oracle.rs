@@ -0,0 +1,192 @@
+use std::sync::{Arc, Mutex};
+
+use ethers::prelude::*;
+use eyre::{eyre, Result};
+use tokio::sync::OnceCell;
+
+use crate::address_book::{price_reference, LpPair};
+use crate::helpers::{address, token_amount};
+use crate::quote::{QuoteKind, QuoteRegistry};
+use crate::token::Token;
+
+/// USD value of a liquidity addition.
+#[derive(Clone, Copy, Debug)]
+pub struct LiquidityValue {
+    pub usd0: f64,
+    pub usd1: f64,
+}
+
+impl LiquidityValue {
+    pub fn total(&self) -> f64 {
+        self.usd0 + self.usd1
+    }
+}
+
+/// The two tokens of the reference pool, oriented.
+struct Reference {
+    native: Token,
+    stable: Token,
+    native_is_token0: bool,
+}
+
+/// Native token USD price of a chain, read from a wrapped native / stablecoin
+/// UniswapV2 like pool and cached per block.
+///
+/// The pool defaults to the one of `address_book::price_reference` and can be
+/// replaced with the `PRICE_POOL_{CHAIN}` env variable. The stablecoins of the
+/// quote registry are worth $1, like the one of the pool.
+pub struct PriceOracle {
+    pool: Address,
+    native: Address,
+    quotes: Arc<QuoteRegistry>,
+    http: Arc<Provider<Http>>,
+    reference: OnceCell<Reference>,
+    cache: Mutex<Option<(U64, f64)>>,
+}
+
+impl std::fmt::Debug for PriceOracle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PriceOracle")
+            .field("pool", &self.pool)
+            .field("native", &self.native)
+            .finish()
+    }
+}
+
+impl PriceOracle {
+    /// `None` on a chain without a reference pool, an invalid `PRICE_POOL_{CHAIN}` is an error.
+    pub fn for_chain(chain: &str, http: Arc<Provider<Http>>, quotes: Arc<QuoteRegistry>) -> Result<Option<Self>> {
+        let reference = match price_reference(chain) {
+            Some(reference) => reference,
+            None => return Ok(None),
+        };
+        let env = format!("PRICE_POOL_{}", chain);
+        let pool = match std::env::var(&env) {
+            Ok(pool) if !pool.is_empty() => pool
+                .parse::<Address>()
+                .map_err(|_| eyre!("Invalid address \"{}\" in {}", pool, env))?,
+            _ => address(reference.pool),
+        };
+        Ok(Some(Self {
+            pool,
+            native: address(reference.native),
+            quotes,
+            http,
+            reference: OnceCell::new(),
+            cache: Mutex::new(None),
+        }))
+    }
+
+    pub fn native(&self) -> Address {
+        self.native
+    }
+
+    async fn reference(&self) -> Result<&Reference> {
+        self.reference
+            .get_or_try_init(|| async {
+                let pair = LpPair::new(self.pool, self.http.clone());
+                let token0 = pair.token_0().call().await?;
+                let token1 = pair.token_1().call().await?;
+                let native_is_token0 = token0 == self.native;
+                let stable = if native_is_token0 { token1 } else { token0 };
+                Ok(Reference {
+                    native: Token::new(self.native, self.http.clone()).await?,
+                    stable: Token::new(stable, self.http.clone()).await?,
+                    native_is_token0,
+                })
+            })
+            .await
+    }
+
+    /// Whether `token` is a stablecoin of the quote registry or of the reference pool.
+    async fn is_stable(&self, token: Address) -> bool {
+        if self.quotes.get(token) == Some(QuoteKind::Stable) {
+            return true;
+        }
+        match self.reference().await {
+            Ok(reference) => reference.stable.address == token,
+            Err(_) => false,
+        }
+    }
+
+    /// USD price of the native token at `block`.
+    pub async fn native_usd(&self, block: U64) -> Result<f64> {
+        if let Some((cached_block, price)) = *self.cache.lock().unwrap() {
+            if cached_block == block {
+                return Ok(price);
+            }
+        }
+
+        let reference = self.reference().await?;
+        let pair = LpPair::new(self.pool, self.http.clone());
+        let reserves = match pair.get_reserves().block(block).call().await {
+            Ok(reserves) => reserves,
+            // Pruned nodes can't read old blocks, the latest price is close enough.
+            Err(_) => pair.get_reserves().call().await?,
+        };
+        let (native_reserve, stable_reserve) = if reference.native_is_token0 {
+            (reserves.0, reserves.1)
+        } else {
+            (reserves.1, reserves.0)
+        };
+        let native = token_amount(native_reserve, reference.native.decimals);
+        let stable = token_amount(stable_reserve, reference.stable.decimals);
+        let price = if native > 0.0 { stable / native } else { 0.0 };
+
+        *self.cache.lock().unwrap() = Some((block, price));
+        Ok(price)
+    }
+
+    /// USD value of `amount` of `token`, when it is the native token or a stablecoin.
+    pub async fn usd_value(&self, token: Address, amount: f64, block: U64) -> Option<f64> {
+        if token == self.native {
+            return self.native_usd(block).await.ok().map(|price| price * amount);
+        }
+        if self.is_stable(token).await {
+            return Some(amount);
+        }
+        None
+    }
+
+    /// USD value of both sides of a liquidity addition to a UniswapV2 like pair. A side
+    /// that can't be priced is worth as much as the other one, a pair taking liquidity
+    /// in equal value.
+    pub async fn liquidity_value(
+        &self,
+        token0: Address,
+        amount0: f64,
+        token1: Address,
+        amount1: f64,
+        block: U64,
+    ) -> Option<LiquidityValue> {
+        let usd0 = self.usd_value(token0, amount0, block).await;
+        let usd1 = self.usd_value(token1, amount1, block).await;
+        match (usd0, usd1) {
+            (Some(usd0), Some(usd1)) => Some(LiquidityValue { usd0, usd1 }),
+            (Some(usd0), None) => Some(LiquidityValue { usd0, usd1: usd0 }),
+            (None, Some(usd1)) => Some(LiquidityValue { usd0: usd1, usd1 }),
+            (None, None) => None,
+        }
+    }
+
+    /// USD value of a position opened on a concentrated liquidity pool. Positions are
+    /// often one sided, a side that can't be priced is left out rather than guessed,
+    /// and the value is unknown when only that side was deposited.
+    pub async fn position_value(
+        &self,
+        token0: Address,
+        amount0: f64,
+        token1: Address,
+        amount1: f64,
+        block: U64,
+    ) -> Option<LiquidityValue> {
+        let usd0 = self.usd_value(token0, amount0, block).await;
+        let usd1 = self.usd_value(token1, amount1, block).await;
+        match (usd0, usd1) {
+            (Some(usd0), Some(usd1)) => Some(LiquidityValue { usd0, usd1 }),
+            (Some(usd0), None) if amount0 > 0.0 => Some(LiquidityValue { usd0, usd1: 0.0 }),
+            (None, Some(usd1)) if amount1 > 0.0 => Some(LiquidityValue { usd0: 0.0, usd1 }),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! `--output json`: every tracker event written as one JSON object per line
+//! (NDJSON), to stdout or a file.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{self, LineWriter, Write};
+
+use chrono::{SecondsFormat, Utc};
+use colored::*;
+use eyre::{Result, WrapErr};
+use serde::Serialize;
+use tokio::sync::{broadcast, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::events::TrackerEvent;
+use crate::timestamp_print;
+
+/// Version of the records, raised on a breaking change.
+pub const OUTPUT_VERSION: u32 = 1;
+/// Block timestamps kept per chain, to date the logs of the last blocks.
+const BLOCK_TIMES: usize = 256;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Colored lines for a human
+    Text,
+    /// One JSON object per event
+    Json,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    version: u32,
+    /// When the event was written, RFC 3339 in UTC.
+    time: String,
+    /// Timestamp of the block of the event, when it was seen.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    block_timestamp: Option<u64>,
+    #[serde(flatten)]
+    event: &'a TrackerEvent,
+}
+
+/// Writes the events until `finish`, which writes the ones already published.
+pub struct EventWriter {
+    task: JoinHandle<()>,
+    finish: oneshot::Sender<()>,
+}
+
+impl EventWriter {
+    /// Writes to the file at `path` (appended to), or to stdout when `None`.
+    pub fn start(events: broadcast::Receiver<TrackerEvent>, path: Option<&str>) -> Result<Self> {
+        let out: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .wrap_err_with(|| format!("can't open output file {}", path))?,
+            ),
+            None => Box::new(io::stdout()),
+        };
+        let (finish, finished) = oneshot::channel();
+        let task = tokio::spawn(write_events(events, LineWriter::new(out), finished));
+        Ok(Self { task, finish })
+    }
+
+    pub async fn finish(self) {
+        let _ = self.finish.send(());
+        let _ = self.task.await;
+    }
+}
+
+async fn write_events(mut events: broadcast::Receiver<TrackerEvent>, mut out: LineWriter<Box<dyn Write + Send>>, mut finished: oneshot::Receiver<()>) {
+    let mut blocks: HashMap<String, BTreeMap<u64, u64>> = HashMap::new();
+    let mut draining = false;
+    loop {
+        let received = if draining {
+            match events.try_recv() {
+                Ok(event) => Ok(event),
+                Err(broadcast::error::TryRecvError::Lagged(skipped)) => Err(broadcast::error::RecvError::Lagged(skipped)),
+                Err(_) => return,
+            }
+        } else {
+            tokio::select! {
+                event = events.recv() => event,
+                _ = &mut finished => {
+                    draining = true;
+                    continue;
+                }
+            }
+        };
+        let event = match received {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                timestamp_print!(Color::Red, Some(false), format!("[OUTPUT] {} events skipped, the output is too slow", skipped));
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+
+        if let TrackerEvent::BlockSeen { chain, number, timestamp, .. } = &event {
+            let times = blocks.entry(chain.clone()).or_default();
+            times.insert(number.as_u64(), timestamp.low_u64());
+            while times.len() > BLOCK_TIMES {
+                times.pop_first();
+            }
+        }
+        let block_timestamp = event
+            .block()
+            .and_then(|block| blocks.get(event.chain())?.get(&block.as_u64()).copied());
+        let record = Record {
+            version: OUTPUT_VERSION,
+            time: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            block_timestamp,
+            event: &event,
+        };
+        let written = serde_json::to_writer(&mut out, &record)
+            .map_err(io::Error::from)
+            .and_then(|_| out.write_all(b"\n"));
+        if let Err(err) = written {
+            timestamp_print!(Color::Red, Some(true), format!("[OUTPUT] {}, events are no longer written", err));
+            return;
+        }
+    }
+}
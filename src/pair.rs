@@ -1,23 +1,55 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use ethers::prelude::*;
+use ethers::{contract::parse_log, prelude::*};
 
-use crate::alert::alert;
+use crate::alert::{LiquidityEvent, Route};
+use crate::backfill::LogPager;
+use crate::contracts::DexKind;
 use crate::dex::Dex;
-use crate::helpers::{token_amount, wei_to_float};
+use crate::events::{LiquidityChange, TrackerEvent};
+use crate::filter::Launch;
+use crate::helpers::{token_amount, u256_amount, u256_to_float};
+use crate::lp::{snapshot, unlock_date, LpHolder, LpTracker};
+use crate::store::{LiquidityKind, LiquidityRecord};
+use crate::subscription::LogSubscriber;
 use crate::timestamp_print;
+use crate::quote::{PairQuote, QuoteKind, Side};
+use crate::simulation::simulate_trade;
 use crate::token::Token;
+use crate::wallet::WalletProfile;
 
 use colored::*;
 use eyre::Result;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+abigen!(
+    UniV2Pair,
+    r#"[
+        event Mint(address indexed sender, uint amount0, uint amount1)
+        event Burn(address indexed sender, uint amount0, uint amount1, address indexed to)
+        event Sync(uint112 reserve0, uint112 reserve1)
+        event Transfer(address indexed from, address indexed to, uint value)
+    ]"#,
+);
+
+/// When a pair counts as rugged: at least `threshold` (0 to 1) of its liquidity
+/// removed within `window` after the first liquidity addition.
+#[derive(Clone, Copy, Debug)]
+pub struct RugSettings {
+    pub threshold: f64,
+    /// A zero window disables the monitoring.
+    pub window: Duration,
+}
 
 pub struct Pair {
     pub dex: Arc<Dex>,
     pub address: Address,
     pub token0: Token,
     pub token1: Token,
-    #[warn(dead_code)]
-    new_token: Token,
+    /// Which token is the one being launched.
+    pub quote: PairQuote,
+    /// Wallet that added the first liquidity.
+    pub deployer: Option<WalletProfile>,
     pub reserves: (U256, U256),
 }
 
@@ -28,12 +60,13 @@ impl Pair {
         token1_address: Address,
         dex: &Arc<Dex>,
         provider: Arc<Provider<Http>>,
-    ) -> Pair {
-        let mut token0: Token = Token::new(token0_address, provider.clone()).await.unwrap();
-        let mut token1: Token = Token::new(token1_address, provider.clone()).await.unwrap();
+    ) -> Result<Pair> {
+        let mut token0: Token = Token::new(token0_address, provider.clone()).await?;
+        let mut token1: Token = Token::new(token1_address, provider.clone()).await?;
         timestamp_print!(
             Color::Green,
             Some(false),
+            dex.chain,
             format!(
                 "TokenIn: {} | {} | {:#066x}",
                 token0.name, token0.symbol, token0.address
@@ -42,32 +75,40 @@ impl Pair {
         timestamp_print!(
             Color::Green,
             Some(false),
+            dex.chain,
             format!(
                 "TokenOut: {} | {} | {:#066x}",
                 token1.name, token1.symbol, token1.address
             )
         );
 
-        let new_token: Token;
-        if token0.symbol == "WETH" {
-            new_token = token1.clone();
-            if dex.verify { token1.verified_contract(dex.chain.clone()).await.unwrap() ;}
-        } else {
-            if dex.verify { token0.verified_contract(dex.chain.clone()).await.unwrap() ;}
-            new_token = token0.clone();
+        let quote = dex.quotes.classify(token0.address, token1.address);
+        timestamp_print!(
+            if quote.base().is_some() { Color::Green } else { Color::Yellow },
+            Some(false),
+            dex.chain,
+            format!("[PAIR] {}", quote.describe(&token0, &token1))
+        );
+        if dex.verify {
+            for token in quote.unknown_tokens(&mut token0, &mut token1) {
+                dex.verify_token(token).await;
+            }
         }
 
-        Pair {
+        Ok(Pair {
             dex: Arc::clone(dex),
             address,
             token0,
             token1,
-            new_token,
+            quote,
+            deployer: None,
             reserves: (0.into(), 0.into()),
-        }
+        })
     }
 
-    pub async fn get_reserves(&mut self, provider: Arc<Provider<Http>>) -> Result<()> {
+    /// Reads the reserves at `block`, the ones left by the liquidity addition when
+    /// backfilled or confirmed later.
+    pub async fn get_reserves(&mut self, provider: Arc<Provider<Http>>, block: U64) -> Result<()> {
         abigen!(
             IUniswapV2Pair,
             r#"[
@@ -75,79 +116,345 @@ impl Pair {
             ]"#,
         );
         let pair = IUniswapV2Pair::new(self.address, provider.clone());
-        let (reserve0, reserve1, _) = pair.get_reserves().call().await?;
+        let (reserve0, reserve1, _) = pair.get_reserves().block(block).call().await?;
         self.reserves = (reserve0.into(), reserve1.into());
         timestamp_print!(
             Color::Green,
             Some(false),
+            self.dex.chain,
             format!(
-                "[PAIR][LIQ] Current reserves: {} {} | {} {}",
-                wei_to_float(self.reserves.0.as_u128()),
+                "[PAIR][LIQ] Reserves: {} {} | {} {}",
+                u256_amount(self.reserves.0, self.token0.decimals),
                 self.token0.symbol,
-                wei_to_float(self.reserves.1.as_u128()),
+                u256_amount(self.reserves.1, self.token1.decimals),
                 self.token1.symbol
             )
         );
         Ok(())
     }
 
+    /// Reserves in token0 and token1.
+    pub fn reserve_amounts(&self) -> (f64, f64) {
+        (
+            u256_amount(self.reserves.0, self.token0.decimals),
+            u256_amount(self.reserves.1, self.token1.decimals),
+        )
+    }
+
+    /// Simulates a buy and a sell of the new token through the dex router, on pairs
+    /// quoted in the wrapped native token. The trade is 1% of the pair native reserve.
+    async fn simulate(&mut self, http: Arc<Provider<Http>>, block: U64) {
+        if self.dex.kind != DexKind::UniV2 {
+            return;
+        }
+        let (token, native, native_reserve) = match self.quote {
+            PairQuote::Base { base: Side::Token0, quote: QuoteKind::Native } => {
+                (&mut self.token0, self.token1.address, self.reserves.1)
+            }
+            PairQuote::Base { base: Side::Token1, quote: QuoteKind::Native } => {
+                (&mut self.token1, self.token0.address, self.reserves.0)
+            }
+            _ => return,
+        };
+        let amount_in = native_reserve / 100;
+        if amount_in.is_zero() {
+            return;
+        }
+        match simulate_trade(http, &self.dex.chain, self.dex.router_address, native, token.address, amount_in, block).await {
+            Ok(simulation) => {
+                timestamp_print!(
+                    if simulation.is_honeypot() { Color::Red } else { Color::Green },
+                    Some(false),
+                    self.dex.chain,
+                    format!("[PAIR][SIM] {} | {}", token.symbol, simulation)
+                );
+                token.simulation = Some(simulation);
+            }
+            Err(err) => {
+                timestamp_print!(
+                    Color::Yellow,
+                    Some(false),
+                    self.dex.chain,
+                    format!("[PAIR][SIM] Simulation failed: {}", err)
+                );
+            }
+        }
+    }
+
+    fn mint_filter(&self) -> Filter {
+        Filter::new()
+            .address(self.address)
+            .topic0(MintFilter::signature())
+    }
+
+    fn liquidity_filter(&self) -> Filter {
+        Filter::new()
+            .address(self.address)
+            .topic0(vec![BurnFilter::signature(), SyncFilter::signature()])
+    }
+
+    fn lp_filter(&self) -> Filter {
+        Filter::new()
+            .address(self.address)
+            .topic0(TransferFilter::signature())
+    }
+
     /// Streams the "Mint" event from the pair address -> addLiquidity
     pub async fn stream_add_liquidity(
         self: &mut Arc<Self>,
-        ws: Arc<Provider<Ws>>,
+        logs: LogSubscriber,
         http: Arc<Provider<Http>>,
         block_number: U64,
     ) -> Result<()> {
-        abigen!(
-            UniV2Pair,
-            r#"[
-                event Mint(address indexed sender, uint amount0, uint amount1)
-            ]"#,
+        let mut mints = logs.subscribe(self.mint_filter(), Some(block_number));
+        timestamp_print!(
+            Color::BrightCyan,
+            Some(false),
+            self.dex.chain,
+            format!(
+                "[PAIR] Listening for Mint events on pair {}, from router {}",
+                self.address, self.dex.router_address
+            )
         );
 
-        //abigen!(
-        //    UniV3Pair,
-        //    r#"[
-        //        event Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)
-        //    ]"#,
-        //);
-
-        let contract = UniV2Pair::new(self.address, ws.clone());
-
-        let events = contract.event::<MintFilter>();
-        let block_events = contract
-            .event::<MintFilter>()
-            .from_block(block_number)
-            .query()
-            .await?;
-
-        let mut amount0: U256 = 0.into();
-        let mut amount1: U256 = 0.into();
-
-        if !block_events.is_empty() {
-            amount0 = block_events[0].amount_0;
-            amount1 = block_events[0].amount_1;
-        } else {
-            let mut stream = events.stream().await?.take(1);
-            timestamp_print!(
-                Color::BrightCyan,
-                Some(false),
-                format!(
-                    "[PAIR] Listening for Mint events on pair {}, from router {}",
-                    self.address, self.dex.router_address
-                )
-            );
+        let (mint, meta): (MintFilter, LogMeta) = loop {
+            match mints.recv().await {
+                Some(log) => {
+                    let meta = LogMeta::from(&log);
+                    if let Ok(mint) = parse_log::<MintFilter>(log) {
+                        break (mint, meta);
+                    }
+                }
+                None => return Ok(()),
+            }
+        };
+        drop(mints);
+        let launch_block = meta.block_number;
+        self.on_add_liquidity(mint, meta, http.clone()).await?;
+        let (rug, lp) = tokio::join!(
+            self.clone().watch_liquidity(logs.clone(), http.clone(), launch_block),
+            self.clone().watch_lp(logs, http, launch_block),
+        );
+        rug.and(lp)
+    }
+
+    /// Follows the "Burn" and "Sync" events of the pair during the rug window after
+    /// its launch -> removeLiquidity. Raises a "liquidity pulled" alert once the share
+    /// of liquidity removed since the launch reaches the threshold.
+    pub async fn watch_liquidity(
+        self: Arc<Self>,
+        logs: LogSubscriber,
+        http: Arc<Provider<Http>>,
+        launch_block: U64,
+    ) -> Result<()> {
+        let rug = self.dex.rug;
+        if rug.window.is_zero() {
+            return Ok(());
+        }
+        let launched_at = launched_at(&http, launch_block).await?;
+        let deadline = launched_at + rug.window.as_secs();
+
+        let mut events = logs.subscribe(self.liquidity_filter(), Some(launch_block));
+        let mut reserves: (u128, u128) = (0, 0);
+        // Share of the launch liquidity still in the pair, burns compound.
+        let mut remaining = 1.0;
+        while let Some(log) = recv_until(&mut events, deadline).await {
+            let meta = LogMeta::from(&log);
+            let burn = match parse_log::<UniV2PairEvents>(log) {
+                Ok(UniV2PairEvents::SyncFilter(sync)) => {
+                    reserves = (sync.reserve_0, sync.reserve_1);
+                    continue;
+                }
+                Ok(UniV2PairEvents::BurnFilter(burn)) => burn,
+                _ => continue,
+            };
+
+            // The pair emits "Sync" before "Burn", the reserves are already the ones left.
+            let before = burn.amount_0.saturating_add(U256::from(reserves.0));
+            if before.is_zero() {
+                continue;
+            }
+            let removed = u256_to_float(burn.amount_0) / u256_to_float(before);
+            remaining *= 1.0 - removed;
+            let pulled = 1.0 - remaining;
+            self.on_remove_liquidity(&burn, &meta, removed, pulled, reserves);
+
+            if pulled >= rug.threshold {
+                self.dex.persist(|store| store.mark_rugged(&self.dex.chain, self.address));
+                let minutes = meta_minutes(&http, &meta, launched_at).await;
+                timestamp_print!(
+                    Color::Red,
+                    Some(true),
+                    self.dex.chain,
+                    format!(
+                        "[PAIR][{}][RUG] {:.1}% of the liquidity pulled from {:#066x}, {} minutes after launch",
+                        self.dex.name,
+                        pulled * 100.0,
+                        self.address,
+                        minutes
+                    )
+                );
+                if self.dex.alert {
+                    let amount_0 = u256_amount(burn.amount_0, self.token0.decimals);
+                    let amount_1 = u256_amount(burn.amount_1, self.token1.decimals);
+                    let left = (
+                        token_amount(reserves.0, self.token0.decimals),
+                        token_amount(reserves.1, self.token1.decimals),
+                    );
+                    let event = LiquidityEvent::rug(&self, pulled, amount_0, amount_1, left, minutes).with_log(&meta);
+                    self.dex.alerts.dispatch(event, &Route::default());
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Follows the LP "Transfer" events of the pair during the LP window after its
+    /// launch -> LP burned, locked or moved by the deployer.
+    pub async fn watch_lp(
+        self: Arc<Self>,
+        logs: LogSubscriber,
+        http: Arc<Provider<Http>>,
+        launch_block: U64,
+    ) -> Result<()> {
+        let window = self.dex.lp_window;
+        if window.is_zero() {
+            return Ok(());
+        }
+        let deadline = launched_at(&http, launch_block).await? + window.as_secs();
 
-            while let Some(Ok(f)) = stream.next().await {
-                amount0 = f.amount_0;
-                amount1 = f.amount_1;
+        // From the launch block, so the first LP minted tells who the deployer is.
+        let mut transfers = logs.subscribe(self.lp_filter(), Some(launch_block));
+        let mut lp = LpTracker::new(self.dex.chain.clone(), self.address);
+        while let Some(log) = recv_until(&mut transfers, deadline).await {
+            if let Ok(transfer) = parse_log::<TransferFilter>(log) {
+                if let Some(holder) = lp.on_transfer(transfer.from, transfer.to, transfer.value) {
+                    self.on_lp_transfer(&lp, holder, &http).await;
+                }
             }
         }
-        let amount_0 = token_amount(amount0.as_u128(), self.token0.decimals);
-        let amount_1 = token_amount(amount1.as_u128(), self.token1.decimals);
+        Ok(())
+    }
+
+    async fn on_lp_transfer(&self, lp: &LpTracker, holder: LpHolder, http: &Arc<Provider<Http>>) {
+        let (color, unlock) = match holder {
+            LpHolder::Locker(locker) => {
+                let unlock = unlock_date(http.clone(), locker, self.address)
+                    .await
+                    .map(|date| format!(" until {}", date))
+                    .unwrap_or_default();
+                (Color::Green, unlock)
+            }
+            LpHolder::Burned => (Color::Green, String::new()),
+            LpHolder::Deployer | LpHolder::Other => (Color::Yellow, String::new()),
+        };
+        timestamp_print!(
+            color,
+            Some(false),
+            self.dex.chain,
+            format!(
+                "[PAIR][LP] LP of {:#066x} {}{} | {}",
+                self.address,
+                holder,
+                unlock,
+                lp.distribution()
+            )
+        );
+    }
+
+    fn on_remove_liquidity(&self, burn: &BurnFilter, meta: &LogMeta, removed: f64, pulled: f64, reserves: (u128, u128)) {
+        self.dex.persist(|store| {
+            store.save_liquidity_event(&LiquidityRecord {
+                chain: self.dex.chain.clone(),
+                pair: self.address,
+                kind: LiquidityKind::Burn,
+                sender: Some(burn.sender),
+                amount0: burn.amount_0,
+                amount1: burn.amount_1,
+                block_number: meta.block_number,
+                tx_hash: Some(meta.transaction_hash),
+                log_index: Some(meta.log_index),
+            })
+        });
+        let reserves = (
+            token_amount(reserves.0, self.token0.decimals),
+            token_amount(reserves.1, self.token1.decimals),
+        );
+        self.dex.events.publish(TrackerEvent::LiquidityRemoved(LiquidityChange {
+            reserves: Some(reserves),
+            ..LiquidityChange::new(&self.dex, self.address, &self.token0, &self.token1, (burn.amount_0, burn.amount_1), meta)
+        }));
+        timestamp_print!(
+            Color::Yellow,
+            Some(false),
+            self.dex.chain,
+            format!(
+                "[PAIR][BURN] {:.1}% of the liquidity removed from {:#066x} ({:.1}% since launch): {} {} | {} {}",
+                removed * 100.0,
+                self.address,
+                pulled * 100.0,
+                u256_amount(burn.amount_0, self.token0.decimals),
+                self.token0.symbol,
+                u256_amount(burn.amount_1, self.token1.decimals),
+                self.token1.symbol
+            )
+        );
+    }
+
+    /// Looks for the first "Mint" of the pair between two blocks -> past addLiquidity.
+    /// Returns false when the pair got no liquidity in that range.
+    pub async fn backfill_add_liquidity(
+        self: &mut Arc<Self>,
+        http: Arc<Provider<Http>>,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<bool> {
+        let mut pager = LogPager::new(
+            http.clone(),
+            self.mint_filter(),
+            from_block.as_u64(),
+            to_block.as_u64(),
+        );
+        while let Some(logs) = pager.next_page().await? {
+            for log in logs {
+                let meta = LogMeta::from(&log);
+                if let Ok(mint) = parse_log::<MintFilter>(log) {
+                    self.on_add_liquidity(mint, meta, http).await?;
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    async fn on_add_liquidity(
+        self: &mut Arc<Self>,
+        mint: MintFilter,
+        meta: LogMeta,
+        http: Arc<Provider<Http>>,
+    ) -> Result<()> {
+        let (amount0, amount1) = (mint.amount_0, mint.amount_1);
+        self.dex.persist(|store| {
+            store.save_liquidity_event(&LiquidityRecord {
+                chain: self.dex.chain.clone(),
+                pair: self.address,
+                kind: LiquidityKind::Mint,
+                sender: Some(mint.sender),
+                amount0,
+                amount1,
+                block_number: meta.block_number,
+                tx_hash: Some(meta.transaction_hash),
+                log_index: Some(meta.log_index),
+            })
+        });
+
+        let amount_0 = u256_amount(amount0, self.token0.decimals);
+        let amount_1 = u256_amount(amount1, self.token1.decimals);
         timestamp_print!(
             Color::Green,
             Some(true),
+            self.dex.chain,
             format!(
                 "[PAIR][{}][FOUND] AddLiquidity detected on pair: {:#066x}",
                 self.dex.name, self.address
@@ -156,38 +463,127 @@ impl Pair {
         timestamp_print!(
             Color::Green,
             Some(false),
+            self.dex.chain,
             format!("[PAIR][LIQ] TokenIn: {:?} {}", amount_0, self.token0.symbol)
         );
         timestamp_print!(
             Color::Green,
             Some(false),
+            self.dex.chain,
             format!(
                 "[PAIR][LIQ] TokenOut: {:?} {}",
                 amount_1, self.token1.symbol
             )
         );
+        let value = match &self.dex.oracle {
+            Some(oracle) => {
+                oracle
+                    .liquidity_value(self.token0.address, amount_0, self.token1.address, amount_1, meta.block_number)
+                    .await
+            }
+            None => None,
+        };
+        if let Some(value) = value {
+            timestamp_print!(
+                Color::Green,
+                Some(false),
+                self.dex.chain,
+                format!("[PAIR][LIQ] Value: ${:.2}", value.total())
+            );
+        }
         let self_mut = Arc::get_mut(self).unwrap();
-        self_mut.get_reserves(http.clone()).await.unwrap();
+        self_mut.get_reserves(http.clone(), meta.block_number).await?;
+        self_mut.dex.events.publish(TrackerEvent::LiquidityAdded(LiquidityChange {
+            reserves: Some(self_mut.reserve_amounts()),
+            value_usd: value.map(|value| value.total()),
+            ..LiquidityChange::new(&self_mut.dex, self_mut.address, &self_mut.token0, &self_mut.token1, (amount0, amount1), &meta)
+        }));
+        self_mut.simulate(http.clone(), meta.block_number).await;
 
         if self_mut.dex.verify {
-            self_mut
-            .new_token
-            .verified_contract(self_mut.dex.chain.clone())
-            .await
-            .unwrap();
+            let chain = self_mut.dex.chain.clone();
+            for token in self_mut.quote.unknown_tokens(&mut self_mut.token0, &mut self_mut.token1) {
+                if let Some(verified) = self_mut.dex.verify_token(token).await {
+                    let address = token.address;
+                    self_mut.dex.persist(|store| store.save_verification(&chain, address, verified));
+                }
+            }
+        }
+        self_mut.deployer = self_mut.dex.profile_deployer(http.clone(), self_mut.address, &meta).await;
+        for token in self_mut.quote.unknown_tokens(&mut self_mut.token0, &mut self_mut.token1) {
+            match token.analyze_risk(&self_mut.dex.chain, http.clone()).await {
+                Ok(_) => self_mut.dex.publish_risk(token),
+                Err(err) => {
+                    timestamp_print!(
+                        Color::Yellow,
+                        Some(false),
+                        self_mut.dex.chain,
+                        format!("[TOKEN] {} | Risk scan failed: {}", token.name, err)
+                    );
+                }
+            }
         }
 
-        let pair = self.clone();
         timestamp_print!(
             Color::Blue,
             Some(false),
-            format!(
-                "[PAIR] Chart: https://dexscreener.com/{}/{:#066x}",
-                self.dex.chain.to_lowercase(),
-                self.address
-            )
+            self.dex.chain,
+            format!("[PAIR] Chart: {}", self.dex.chart_url(self.address))
         );
-        if self.dex.alert { alert(pair, amount_0, amount_1).await.unwrap() }
+        // Shown in the alert and used by the filters.
+        let lp = match self.dex.alert {
+            true => {
+                let deployer = self.deployer.as_ref().map(|deployer| deployer.address);
+                snapshot(http.clone(), &self.dex.chain, self.address, deployer).await
+            }
+            false => None,
+        };
+        let launch = Launch {
+            dex: &self.dex,
+            token0: &self.token0,
+            token1: &self.token1,
+            quote: self.quote,
+            amounts: (amount_0, amount_1),
+            value,
+            lp,
+            deployer: self.deployer.as_ref(),
+        };
+        if let Some(route) = self.dex.alert_route(&launch) {
+            let event = LiquidityEvent::liquidity(self, amount_0, amount_1, value, lp).with_log(&meta);
+            self.dex.alerts.dispatch(event, &route);
+        }
         Ok(())
     }
 }
+
+/// Timestamp of the launch block, now when the node doesn't know it.
+async fn launched_at(http: &Provider<Http>, launch_block: U64) -> Result<u64> {
+    Ok(match http.get_block(launch_block).await? {
+        Some(block) => block.timestamp.as_u64(),
+        None => chrono::Utc::now().timestamp() as u64,
+    })
+}
+
+/// Next log of the subscription before `deadline` (unix time), removed logs being
+/// skipped. `None` once the deadline is past or the subscription ended.
+async fn recv_until(logs: &mut UnboundedReceiver<Log>, deadline: u64) -> Option<Log> {
+    loop {
+        let left = deadline.saturating_sub(chrono::Utc::now().timestamp() as u64);
+        let log = tokio::select! {
+            log = logs.recv() => log,
+            _ = tokio::time::sleep(Duration::from_secs(left)) => None,
+        }?;
+        if log.removed != Some(true) {
+            return Some(log);
+        }
+    }
+}
+
+/// Minutes between the launch and the block of `meta`.
+async fn meta_minutes(http: &Provider<Http>, meta: &LogMeta, launched_at: u64) -> u64 {
+    let timestamp = match http.get_block(meta.block_number).await {
+        Ok(Some(block)) => block.timestamp.as_u64(),
+        _ => chrono::Utc::now().timestamp() as u64,
+    };
+    timestamp.saturating_sub(launched_at) / 60
+}
@@ -0,0 +1,360 @@
+use std::sync::Arc;
+
+use ethers::{contract::parse_log, prelude::*};
+
+use crate::alert::LiquidityEvent;
+use crate::backfill::LogPager;
+use crate::dex::Dex;
+use crate::events::{LiquidityChange, TrackerEvent};
+use crate::filter::Launch;
+use crate::helpers::{sqrt_price_x96_to_price, tick_to_price, u256_amount};
+use crate::store::{LiquidityKind, LiquidityRecord};
+use crate::subscription::LogSubscriber;
+use crate::timestamp_print;
+use crate::quote::PairQuote;
+use crate::token::Token;
+use crate::wallet::WalletProfile;
+
+use colored::*;
+use eyre::Result;
+
+abigen!(
+    UniV3Pool,
+    r#"[
+        function slot0() external view returns (uint160 sqrtPriceX96, int24 tick, uint16 observationIndex, uint16 observationCardinality, uint16 observationCardinalityNext, uint8 feeProtocol, bool unlocked)
+        event Mint(address sender, address indexed owner, int24 indexed tickLower, int24 indexed tickUpper, uint128 amount, uint256 amount0, uint256 amount1)
+    ]"#
+);
+
+/// A concentrated liquidity pool (UniswapV3 like).
+pub struct Pool {
+    pub dex: Arc<Dex>,
+    pub address: Address,
+    pub token0: Token,
+    pub token1: Token,
+    /// Fee tier in hundredths of a bip (500 = 0.05%).
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub sqrt_price_x96: U256,
+    pub tick: i32,
+    /// Which token is the one being launched.
+    pub quote: PairQuote,
+    /// Wallet that added the first liquidity.
+    pub deployer: Option<WalletProfile>,
+}
+
+/// A decoded V3 `Mint`, i.e. a position opened on the pool.
+#[derive(Clone, Debug)]
+pub struct Position {
+    pub owner: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub liquidity: u128,
+    pub amount0: f64,
+    pub amount1: f64,
+}
+
+impl Pool {
+    pub async fn new(
+        address: Address,
+        token0_address: Address,
+        token1_address: Address,
+        fee: u32,
+        tick_spacing: i32,
+        dex: &Arc<Dex>,
+        provider: Arc<Provider<Http>>,
+    ) -> Result<Pool> {
+        let mut token0: Token = Token::new(token0_address, provider.clone()).await?;
+        let mut token1: Token = Token::new(token1_address, provider.clone()).await?;
+        timestamp_print!(
+            Color::Green,
+            Some(false),
+            dex.chain,
+            format!(
+                "TokenIn: {} | {} | {:#066x}",
+                token0.name, token0.symbol, token0.address
+            )
+        );
+        timestamp_print!(
+            Color::Green,
+            Some(false),
+            dex.chain,
+            format!(
+                "TokenOut: {} | {} | {:#066x}",
+                token1.name, token1.symbol, token1.address
+            )
+        );
+        timestamp_print!(
+            Color::Green,
+            Some(false),
+            dex.chain,
+            format!(
+                "[POOL] Fee tier: {}% | Tick spacing: {}",
+                fee as f64 / 10_000.0,
+                tick_spacing
+            )
+        );
+
+        let quote = dex.quotes.classify(token0.address, token1.address);
+        timestamp_print!(
+            if quote.base().is_some() { Color::Green } else { Color::Yellow },
+            Some(false),
+            dex.chain,
+            format!("[POOL] {}", quote.describe(&token0, &token1))
+        );
+        if dex.verify {
+            for token in quote.unknown_tokens(&mut token0, &mut token1) {
+                dex.verify_token(token).await;
+            }
+        }
+
+        Ok(Pool {
+            dex: Arc::clone(dex),
+            address,
+            token0,
+            token1,
+            fee,
+            tick_spacing,
+            sqrt_price_x96: 0.into(),
+            tick: 0,
+            quote,
+            deployer: None,
+        })
+    }
+
+    /// Price of token0 expressed in token1, from the `sqrtPriceX96` read by `get_slot0`.
+    pub fn price(&self) -> f64 {
+        sqrt_price_x96_to_price(self.sqrt_price_x96, self.token0.decimals, self.token1.decimals)
+    }
+
+    /// Price of token0 expressed in token1 at the given tick.
+    pub fn price_at_tick(&self, tick: i32) -> f64 {
+        tick_to_price(tick, self.token0.decimals, self.token1.decimals)
+    }
+
+    /// Reads `slot0` at `block`, the price right after the first position when
+    /// backfilled or confirmed later.
+    pub async fn get_slot0(&mut self, provider: Arc<Provider<Http>>, block: U64) -> Result<()> {
+        let pool = UniV3Pool::new(self.address, provider.clone());
+        let (sqrt_price_x96, tick, _, _, _, _, _) = pool.slot_0().block(block).call().await?;
+        self.sqrt_price_x96 = sqrt_price_x96;
+        self.tick = tick;
+        timestamp_print!(
+            Color::Green,
+            Some(false),
+            self.dex.chain,
+            format!(
+                "[POOL][PRICE] Price: {} {} per {} | tick {}",
+                self.price(),
+                self.token1.symbol,
+                self.token0.symbol,
+                self.tick
+            )
+        );
+        Ok(())
+    }
+
+    fn mint_filter(&self) -> Filter {
+        Filter::new()
+            .address(self.address)
+            .topic0(MintFilter::signature())
+    }
+
+    /// Streams the "Mint" event from the pool address -> position opened
+    pub async fn stream_add_liquidity(
+        self: &mut Arc<Self>,
+        logs: LogSubscriber,
+        http: Arc<Provider<Http>>,
+        block_number: U64,
+    ) -> Result<()> {
+        let mut mints = logs.subscribe(self.mint_filter(), Some(block_number));
+        timestamp_print!(
+            Color::BrightCyan,
+            Some(false),
+            self.dex.chain,
+            format!(
+                "[POOL] Listening for Mint events on pool {}, from router {}",
+                self.address, self.dex.router_address
+            )
+        );
+
+        let mut mint: Option<(MintFilter, LogMeta)> = None;
+        while let Some(log) = mints.recv().await {
+            let meta = LogMeta::from(&log);
+            if let Ok(event) = parse_log::<MintFilter>(log) {
+                mint = Some((event, meta));
+                break;
+            }
+        }
+        drop(mints);
+        match mint {
+            Some((mint, meta)) => self.on_add_liquidity(mint, meta, http).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Looks for the first "Mint" of the pool between two blocks -> past position.
+    /// Returns false when no position was opened in that range.
+    pub async fn backfill_add_liquidity(
+        self: &mut Arc<Self>,
+        http: Arc<Provider<Http>>,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<bool> {
+        let mut pager = LogPager::new(
+            http.clone(),
+            self.mint_filter(),
+            from_block.as_u64(),
+            to_block.as_u64(),
+        );
+        while let Some(logs) = pager.next_page().await? {
+            for log in logs {
+                let meta = LogMeta::from(&log);
+                if let Ok(mint) = parse_log::<MintFilter>(log) {
+                    self.on_add_liquidity(mint, meta, http).await?;
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    async fn on_add_liquidity(
+        self: &mut Arc<Self>,
+        mint: MintFilter,
+        meta: LogMeta,
+        http: Arc<Provider<Http>>,
+    ) -> Result<()> {
+        self.dex.persist(|store| {
+            store.save_liquidity_event(&LiquidityRecord {
+                chain: self.dex.chain.clone(),
+                pair: self.address,
+                kind: LiquidityKind::Mint,
+                sender: Some(mint.sender),
+                amount0: mint.amount_0,
+                amount1: mint.amount_1,
+                block_number: meta.block_number,
+                tx_hash: Some(meta.transaction_hash),
+                log_index: Some(meta.log_index),
+            })
+        });
+
+        let position = Position {
+            owner: mint.owner,
+            tick_lower: mint.tick_lower,
+            tick_upper: mint.tick_upper,
+            liquidity: mint.amount,
+            amount0: u256_amount(mint.amount_0, self.token0.decimals),
+            amount1: u256_amount(mint.amount_1, self.token1.decimals),
+        };
+
+        timestamp_print!(
+            Color::Green,
+            Some(true),
+            self.dex.chain,
+            format!(
+                "[POOL][{}][FOUND] Position opened on pool: {:#066x}",
+                self.dex.name, self.address
+            )
+        );
+        timestamp_print!(
+            Color::Green,
+            Some(false),
+            self.dex.chain,
+            format!("[POOL][LIQ] TokenIn: {:?} {}", position.amount0, self.token0.symbol)
+        );
+        timestamp_print!(
+            Color::Green,
+            Some(false),
+            self.dex.chain,
+            format!("[POOL][LIQ] TokenOut: {:?} {}", position.amount1, self.token1.symbol)
+        );
+        timestamp_print!(
+            Color::Green,
+            Some(false),
+            self.dex.chain,
+            format!(
+                "[POOL][LIQ] Owner: {:#x} | Liquidity: {} | Range: [{}, {}] ticks -> [{}, {}] {} per {}",
+                position.owner,
+                position.liquidity,
+                position.tick_lower,
+                position.tick_upper,
+                self.price_at_tick(position.tick_lower),
+                self.price_at_tick(position.tick_upper),
+                self.token1.symbol,
+                self.token0.symbol
+            )
+        );
+
+        let value = match &self.dex.oracle {
+            Some(oracle) => {
+                oracle
+                    .position_value(self.token0.address, position.amount0, self.token1.address, position.amount1, meta.block_number)
+                    .await
+            }
+            None => None,
+        };
+        if let Some(value) = value {
+            timestamp_print!(
+                Color::Green,
+                Some(false),
+                self.dex.chain,
+                format!("[POOL][LIQ] Value: ${:.2}", value.total())
+            );
+        }
+
+        self.dex.events.publish(TrackerEvent::LiquidityAdded(LiquidityChange {
+            value_usd: value.map(|value| value.total()),
+            ..LiquidityChange::new(&self.dex, self.address, &self.token0, &self.token1, (mint.amount_0, mint.amount_1), &meta)
+        }));
+
+        let self_mut = Arc::get_mut(self).unwrap();
+        self_mut.get_slot0(http.clone(), meta.block_number).await?;
+
+        if self_mut.dex.verify {
+            let chain = self_mut.dex.chain.clone();
+            for token in self_mut.quote.unknown_tokens(&mut self_mut.token0, &mut self_mut.token1) {
+                if let Some(verified) = self_mut.dex.verify_token(token).await {
+                    let address = token.address;
+                    self_mut.dex.persist(|store| store.save_verification(&chain, address, verified));
+                }
+            }
+        }
+        self_mut.deployer = self_mut.dex.profile_deployer(http.clone(), self_mut.address, &meta).await;
+        for token in self_mut.quote.unknown_tokens(&mut self_mut.token0, &mut self_mut.token1) {
+            match token.analyze_risk(&self_mut.dex.chain, http.clone()).await {
+                Ok(_) => self_mut.dex.publish_risk(token),
+                Err(err) => {
+                    timestamp_print!(
+                        Color::Yellow,
+                        Some(false),
+                        self_mut.dex.chain,
+                        format!("[TOKEN] {} | Risk scan failed: {}", token.name, err)
+                    );
+                }
+            }
+        }
+
+        timestamp_print!(
+            Color::Blue,
+            Some(false),
+            self.dex.chain,
+            format!("[POOL] Chart: {}", self.dex.chart_url(self.address))
+        );
+        let launch = Launch {
+            dex: &self.dex,
+            token0: &self.token0,
+            token1: &self.token1,
+            quote: self.quote,
+            amounts: (position.amount0, position.amount1),
+            value,
+            lp: None,
+            deployer: self.deployer.as_ref(),
+        };
+        if let Some(route) = self.dex.alert_route(&launch) {
+            let event = LiquidityEvent::position(self, &position, value).with_log(&meta);
+            self.dex.alerts.dispatch(event, &route);
+        }
+        Ok(())
+    }
+}
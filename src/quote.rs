@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use ethers::prelude::*;
+use eyre::{eyre, Result};
+
+use crate::address_book::{
+    BUSD_BSC, DAI_MAINNET, USDC_ARBI, USDC_AVAX, USDC_BSC, USDC_CRONOS, USDC_FANTOM,
+    USDC_MAINNET, USDC_POLYGON, USDT_ARBI, USDT_AVAX, USDT_BSC, USDT_CRONOS, USDT_FANTOM,
+    USDT_MAINNET, USDT_POLYGON, WAVAX, WBNB, WCRO, WETH_ARBI, WETH_MAINNET, WFTM, WMATIC,
+};
+use crate::helpers::address;
+use crate::token::Token;
+
+/// Why a token is considered a quote asset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuoteKind {
+    /// Wrapped native token of the chain (WETH, WBNB, WMATIC...).
+    Native,
+    Stable,
+    /// Added with the `QUOTE_TOKENS_{CHAIN}` env variable.
+    Extra,
+}
+
+/// Side of a pair, token0 or token1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Token0,
+    Token1,
+}
+
+/// Base / quote classification of the two tokens of a pair.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PairQuote {
+    /// The usual launch: a new token (`base`) against a known quote asset.
+    Base { base: Side, quote: QuoteKind },
+    /// Neither token is a known quote asset.
+    Unquoted,
+    /// Both tokens are quote assets (WETH/USDC...), nothing new is listed.
+    QuoteOnly,
+}
+
+impl PairQuote {
+    /// Side of the token that was just listed, `None` when it can't be told apart.
+    pub fn base(&self) -> Option<Side> {
+        match self {
+            PairQuote::Base { base, .. } => Some(*base),
+            _ => None,
+        }
+    }
+
+    /// Tokens that may have just been launched: the base token, or both of them
+    /// when no quote asset is known.
+    pub fn unknown_tokens<'a>(&self, token0: &'a mut Token, token1: &'a mut Token) -> Vec<&'a mut Token> {
+        match self {
+            PairQuote::Base { base: Side::Token0, .. } => vec![token0],
+            PairQuote::Base { base: Side::Token1, .. } => vec![token1],
+            PairQuote::Unquoted => vec![token0, token1],
+            PairQuote::QuoteOnly => Vec::new(),
+        }
+    }
+
+    /// Human readable classification, for the console and the alerts.
+    pub fn describe(&self, token0: &Token, token1: &Token) -> String {
+        match self {
+            PairQuote::Base { base, quote } => {
+                let (base, quote_token) = match base {
+                    Side::Token0 => (token0, token1),
+                    Side::Token1 => (token1, token0),
+                };
+                format!("{} quoted in {} ({:?})", base.symbol, quote_token.symbol, quote)
+            }
+            PairQuote::Unquoted => format!(
+                "No known quote asset in {}/{}",
+                token0.symbol, token1.symbol
+            ),
+            PairQuote::QuoteOnly => format!(
+                "{}/{} are both quote assets",
+                token0.symbol, token1.symbol
+            ),
+        }
+    }
+}
+
+/// Quote assets of a chain, matched by address so a token merely named "WETH"
+/// is never mistaken for one.
+#[derive(Clone, Debug, Default)]
+pub struct QuoteRegistry {
+    assets: HashMap<Address, QuoteKind>,
+}
+
+impl QuoteRegistry {
+    /// Built-in assets of the chain plus the comma separated addresses of the
+    /// `QUOTE_TOKENS_{CHAIN}` env variable.
+    pub fn for_chain(chain: &str) -> Result<Self> {
+        let mut assets: HashMap<Address, QuoteKind> = builtin_quotes(chain)
+            .iter()
+            .map(|(token, kind)| (address(token), *kind))
+            .collect();
+
+        let env = format!("QUOTE_TOKENS_{}", chain);
+        if let Ok(extras) = std::env::var(&env) {
+            for extra in extras.split(',').map(str::trim).filter(|extra| !extra.is_empty()) {
+                let token = extra
+                    .parse::<Address>()
+                    .map_err(|_| eyre!("Invalid address \"{}\" in {}", extra, env))?;
+                assets.entry(token).or_insert(QuoteKind::Extra);
+            }
+        }
+        Ok(Self { assets })
+    }
+
+    pub fn get(&self, token: Address) -> Option<QuoteKind> {
+        self.assets.get(&token).copied()
+    }
+
+    pub fn classify(&self, token0: Address, token1: Address) -> PairQuote {
+        match (self.get(token0), self.get(token1)) {
+            (Some(_), Some(_)) => PairQuote::QuoteOnly,
+            (Some(quote), None) => PairQuote::Base { base: Side::Token1, quote },
+            (None, Some(quote)) => PairQuote::Base { base: Side::Token0, quote },
+            (None, None) => PairQuote::Unquoted,
+        }
+    }
+}
+
+fn builtin_quotes(chain: &str) -> &'static [(&'static str, QuoteKind)] {
+    use QuoteKind::*;
+    match chain {
+        "ETHEREUM" => &[
+            (WETH_MAINNET, Native),
+            (USDC_MAINNET, Stable),
+            (USDT_MAINNET, Stable),
+            (DAI_MAINNET, Stable),
+        ],
+        "ARBITRUM" => &[(WETH_ARBI, Native), (USDC_ARBI, Stable), (USDT_ARBI, Stable)],
+        "POLYGON" => &[(WMATIC, Native), (USDC_POLYGON, Stable), (USDT_POLYGON, Stable)],
+        "BSC" => &[
+            (WBNB, Native),
+            (BUSD_BSC, Stable),
+            (USDT_BSC, Stable),
+            (USDC_BSC, Stable),
+        ],
+        "FANTOM" => &[(WFTM, Native), (USDC_FANTOM, Stable), (USDT_FANTOM, Stable)],
+        "AVALANCHE" => &[(WAVAX, Native), (USDC_AVAX, Stable), (USDT_AVAX, Stable)],
+        "CRONOS" => &[(WCRO, Native), (USDC_CRONOS, Stable), (USDT_CRONOS, Stable)],
+        _ => &[],
+    }
+}
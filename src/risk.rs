@@ -0,0 +1,317 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use ethers::utils::id;
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+abigen!(
+    Ownable,
+    r#"[
+        function owner() external view returns (address)
+    ]"#
+);
+
+/// `keccak256("eip1967.proxy.implementation") - 1`
+const EIP1967_IMPLEMENTATION_SLOT: &str =
+    "0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc";
+/// `keccak256("eip1967.proxy.beacon") - 1`
+const EIP1967_BEACON_SLOT: &str =
+    "0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50";
+/// Runtime code prefix of the EIP-1167 minimal proxies, followed by the implementation.
+const EIP1167_PREFIX: [u8; 10] = [0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x73];
+const DEAD: &str = "0x000000000000000000000000000000000000dead";
+
+const MINT: &[&str] = &["mint(address,uint256)", "mint(uint256)", "mintTo(address,uint256)"];
+const BLACKLIST: &[&str] = &[
+    "blacklist(address)",
+    "isBlacklisted(address)",
+    "addToBlacklist(address)",
+    "setBlacklist(address,bool)",
+    "addBots(address[])",
+    "setBots(address[])",
+    "bots(address)",
+];
+const WHITELIST: &[&str] = &[
+    "whitelist(address)",
+    "isWhitelisted(address)",
+    "addToWhitelist(address)",
+    "setWhitelist(address,bool)",
+];
+const PAUSABLE: &[&str] = &["pause()", "unpause()", "paused()", "setTradingEnabled(bool)"];
+const MAX_TX: &[&str] = &[
+    "_maxTxAmount()",
+    "maxTxAmount()",
+    "setMaxTxAmount(uint256)",
+    "setMaxTxPercent(uint256)",
+];
+const MAX_WALLET: &[&str] = &[
+    "_maxWalletSize()",
+    "maxWalletSize()",
+    "maxWallet()",
+    "setMaxWalletSize(uint256)",
+    "_maxWalletToken()",
+];
+
+/// A risky capability found on a token contract.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskSignal {
+    /// `owner()` is neither the zero nor the dead address.
+    OwnerNotRenounced(Address),
+    Mintable,
+    Blacklist,
+    Whitelist,
+    Pausable,
+    MaxTxLimit,
+    MaxWalletLimit,
+    /// Upgradeable (EIP-1967) or minimal (EIP-1167) proxy, with its implementation when known.
+    Proxy(Option<Address>),
+    SelfDestruct,
+}
+
+impl RiskSignal {
+    /// Contribution of the signal to the risk score.
+    pub fn weight(&self) -> u32 {
+        match self {
+            RiskSignal::OwnerNotRenounced(_) => 10,
+            RiskSignal::Mintable => 25,
+            RiskSignal::Blacklist => 20,
+            RiskSignal::Whitelist => 10,
+            RiskSignal::Pausable => 15,
+            RiskSignal::MaxTxLimit => 5,
+            RiskSignal::MaxWalletLimit => 5,
+            RiskSignal::Proxy(_) => 20,
+            RiskSignal::SelfDestruct => 25,
+        }
+    }
+}
+
+impl fmt::Display for RiskSignal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RiskSignal::OwnerNotRenounced(owner) => write!(f, "owner {:#x}", owner),
+            RiskSignal::Mintable => write!(f, "mintable"),
+            RiskSignal::Blacklist => write!(f, "blacklist"),
+            RiskSignal::Whitelist => write!(f, "whitelist"),
+            RiskSignal::Pausable => write!(f, "pausable"),
+            RiskSignal::MaxTxLimit => write!(f, "max tx"),
+            RiskSignal::MaxWalletLimit => write!(f, "max wallet"),
+            RiskSignal::Proxy(Some(implementation)) => write!(f, "proxy to {:#x}", implementation),
+            RiskSignal::Proxy(None) => write!(f, "proxy"),
+            RiskSignal::SelfDestruct => write!(f, "selfdestruct"),
+        }
+    }
+}
+
+/// Risk signals of a token contract, scored from 0 (nothing found) to 100.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RiskReport {
+    pub signals: Vec<RiskSignal>,
+    pub score: u32,
+}
+
+impl RiskReport {
+    fn new(signals: Vec<RiskSignal>) -> Self {
+        let score = signals.iter().map(RiskSignal::weight).sum::<u32>().min(100);
+        Self { signals, score }
+    }
+
+    pub fn has(&self, signal: &RiskSignal) -> bool {
+        self.signals
+            .iter()
+            .any(|found| std::mem::discriminant(found) == std::mem::discriminant(signal))
+    }
+}
+
+impl fmt::Display for RiskReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Risk score {}/100", self.score)?;
+        if !self.signals.is_empty() {
+            let signals: Vec<String> = self.signals.iter().map(|signal| signal.to_string()).collect();
+            write!(f, ": {}", signals.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Inspects the deployed bytecode of a token (and of its implementation when it is a
+/// proxy) and its `owner()` for risky capabilities.
+pub async fn analyze(http: Arc<Provider<Http>>, token: Address) -> Result<RiskReport> {
+    let mut signals = Vec::new();
+
+    let owner = Ownable::new(token, http.clone()).owner().call().await;
+    if let Ok(owner) = owner {
+        if owner != Address::zero() && owner != DEAD.parse()? {
+            signals.push(RiskSignal::OwnerNotRenounced(owner));
+        }
+    }
+
+    let mut code = http.get_code(token, None).await?;
+    if let Some(implementation) = proxy_implementation(&http, token, &code).await? {
+        signals.push(RiskSignal::Proxy(implementation));
+        if let Some(implementation) = implementation {
+            code = http.get_code(implementation, None).await?;
+        }
+    }
+
+    let scan = CodeScan::scan(&code);
+    let checks = [
+        (MINT, RiskSignal::Mintable),
+        (BLACKLIST, RiskSignal::Blacklist),
+        (WHITELIST, RiskSignal::Whitelist),
+        (PAUSABLE, RiskSignal::Pausable),
+        (MAX_TX, RiskSignal::MaxTxLimit),
+        (MAX_WALLET, RiskSignal::MaxWalletLimit),
+    ];
+    for (functions, signal) in checks {
+        if functions.iter().any(|function| scan.selectors.contains(&id(function))) {
+            signals.push(signal);
+        }
+    }
+    if scan.self_destruct {
+        signals.push(RiskSignal::SelfDestruct);
+    }
+
+    Ok(RiskReport::new(signals))
+}
+
+/// `Some` when the contract is a proxy, with the implementation it points to if it can be read.
+async fn proxy_implementation(
+    http: &Provider<Http>,
+    token: Address,
+    code: &Bytes,
+) -> Result<Option<Option<Address>>> {
+    if code.len() >= EIP1167_PREFIX.len() + 20 && code[..EIP1167_PREFIX.len()] == EIP1167_PREFIX {
+        let start = EIP1167_PREFIX.len();
+        return Ok(Some(Some(Address::from_slice(&code[start..start + 20]))));
+    }
+
+    let slot = http
+        .get_storage_at(token, EIP1967_IMPLEMENTATION_SLOT.parse()?, None)
+        .await?;
+    if !slot.is_zero() {
+        return Ok(Some(Some(Address::from(slot))));
+    }
+    let beacon = http
+        .get_storage_at(token, EIP1967_BEACON_SLOT.parse()?, None)
+        .await?;
+    if !beacon.is_zero() {
+        return Ok(Some(None));
+    }
+    Ok(None)
+}
+
+/// What a walk over the opcodes of a runtime bytecode found.
+struct CodeScan {
+    /// 4 bytes values pushed on the stack, i.e. the selectors of the function dispatcher.
+    selectors: HashSet<[u8; 4]>,
+    self_destruct: bool,
+}
+
+impl CodeScan {
+    fn scan(code: &[u8]) -> Self {
+        let code = strip_metadata(code);
+        let mut selectors = HashSet::new();
+        let mut self_destruct = false;
+        let mut i = 0;
+        while i < code.len() {
+            let opcode = code[i];
+            match opcode {
+                // PUSH1..PUSH32, the pushed bytes are data and not opcodes.
+                0x60..=0x7f => {
+                    let size = (opcode - 0x5f) as usize;
+                    let data = &code[(i + 1).min(code.len())..(i + 1 + size).min(code.len())];
+                    // Selectors starting with a zero byte are pushed with PUSH3.
+                    if data.len() == 4 || data.len() == 3 {
+                        let mut selector = [0u8; 4];
+                        selector[4 - data.len()..].copy_from_slice(data);
+                        selectors.insert(selector);
+                    }
+                    i += size;
+                }
+                0xff => self_destruct = true,
+                _ => {}
+            }
+            i += 1;
+        }
+        Self {
+            selectors,
+            self_destruct,
+        }
+    }
+}
+
+/// Drops the CBOR metadata appended by solc, it is data and could contain `0xff` bytes.
+fn strip_metadata(code: &[u8]) -> &[u8] {
+    if code.len() < 2 {
+        return code;
+    }
+    let length = u16::from_be_bytes([code[code.len() - 2], code[code.len() - 1]]) as usize;
+    if length + 2 <= code.len() && (0xa1..=0xa3).contains(&code[code.len() - 2 - length]) {
+        &code[..code.len() - 2 - length]
+    } else {
+        code
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `PUSH4 <selector> EQ PUSH2 0x0040 JUMPI`, a branch of the function dispatcher.
+    fn dispatch(selector: [u8; 4]) -> Vec<u8> {
+        [vec![0x63], selector.to_vec(), vec![0x14, 0x61, 0x00, 0x40, 0x57]].concat()
+    }
+
+    /// solc metadata, a CBOR map followed by its length, with `bytes` in it.
+    fn metadata(bytes: &[u8]) -> Vec<u8> {
+        let cbor = [&[0xa1, 0x64, b'i', b'p', b'f', b's', 0x58, bytes.len() as u8][..], bytes].concat();
+        [cbor.clone(), (cbor.len() as u16).to_be_bytes().to_vec()].concat()
+    }
+
+    #[test]
+    fn finds_the_dispatched_selectors() {
+        let mint = id("mint(address,uint256)");
+        // `PUSH3 0x123456` for a selector starting with a zero byte.
+        let code = [dispatch(mint), vec![0x62, 0x12, 0x34, 0x56, 0x14, 0x00]].concat();
+
+        let scan = CodeScan::scan(&code);
+        assert!(scan.selectors.contains(&mint));
+        assert!(scan.selectors.contains(&[0x00, 0x12, 0x34, 0x56]));
+        // The jump destination is a PUSH2, not a selector.
+        assert!(!scan.selectors.iter().any(|selector| selector[2..] == [0x00, 0x40]));
+        assert!(!scan.self_destruct);
+    }
+
+    #[test]
+    fn skips_the_pushed_data() {
+        assert!(!CodeScan::scan(&[0x61, 0xff, 0xff, 0x00]).self_destruct);
+        assert!(CodeScan::scan(&[0x60, 0x00, 0xff]).self_destruct);
+    }
+
+    #[test]
+    fn ignores_the_metadata() {
+        let code = dispatch(id("pause()"));
+        let blacklist = id("blacklist(address)");
+        // `PUSH4 <selector>` and `SELFDESTRUCT` inside the metadata.
+        let hidden = [&[0x63u8][..], &blacklist[..], &[0xff][..]].concat();
+        let with_metadata = [code.clone(), metadata(&hidden)].concat();
+
+        assert_eq!(strip_metadata(&with_metadata), &code[..]);
+        let scan = CodeScan::scan(&with_metadata);
+        assert!(scan.selectors.contains(&id("pause()")));
+        assert!(!scan.selectors.contains(&blacklist));
+        assert!(!scan.self_destruct);
+    }
+
+    #[test]
+    fn keeps_code_without_metadata() {
+        // Ends with `RETURN`, whose bytes don't read as a metadata length.
+        let code = [0x60, 0x00, 0x80, 0xf3];
+        assert_eq!(strip_metadata(&code), &code[..]);
+        assert_eq!(strip_metadata(&[0xfe]), &[0xfe][..]);
+    }
+}
@@ -0,0 +1,484 @@
+use std::fmt;
+use std::sync::Arc;
+
+use ethers::abi::AbiDecode;
+use ethers::prelude::*;
+use ethers::providers::{spoof, RawCall};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::address_book::UniV2Router;
+use crate::helpers::u256_to_float;
+use crate::timestamp_print;
+use colored::*;
+
+abigen!(SimulatedToken, "src/abi/ERC20.json");
+
+/// Fake accounts of the simulation, they only exist in the overridden state.
+const TRADER: &str = "0x000000000000000000000000000000000000babe";
+const RECIPIENT: &str = "0x000000000000000000000000000000000000cafe";
+/// Code given to the trader account: it makes the calls of its calldata one after the
+/// other, each encoded as `to`, `value` and the data length (32 bytes words) followed
+/// by the data padded to 32 bytes. It returns for each call a success word, the length
+/// of the return data (or revert data) and that data, padded the same way.
+const BATCH_CODE: &str = "600060005b80361115605e57806040013580826060018460400137600060008285604001856020013586355af183523d808460200152806000856040013e601f0160051c60051b60400183019250601f0160051c60051b606001016004565b506000f3";
+/// Selector of `Error(string)`, the revert data of a `require` with a message.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+
+/// Outcome of a simulated buy, transfer and sell of a new token.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Simulation {
+    /// Revert reason of the buy, trading is often not enabled yet at launch.
+    pub buy_revert: Option<String>,
+    pub sell_revert: Option<String>,
+    /// Share of the expected amount kept by the token on each operation (0 to 1).
+    pub buy_tax: Option<f64>,
+    pub sell_tax: Option<f64>,
+    pub transfer_tax: Option<f64>,
+}
+
+impl Simulation {
+    /// The token can be bought but not sold back, or only at a loss of nearly everything.
+    pub fn is_honeypot(&self) -> bool {
+        self.buy_revert.is_none()
+            && (self.sell_revert.is_some() || self.sell_tax.is_some_and(|tax| tax >= 0.9))
+    }
+}
+
+impl fmt::Display for Simulation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |tax: Option<f64>| match tax {
+            Some(tax) => format!("{:.1}%", tax * 100.0),
+            None => "-".to_string(),
+        };
+        if let Some(reason) = &self.buy_revert {
+            return write!(f, "Buy reverts: {}", reason);
+        }
+        write!(
+            f,
+            "Buy tax: {} | Sell tax: {} | Transfer tax: {}",
+            percent(self.buy_tax),
+            percent(self.sell_tax),
+            percent(self.transfer_tax)
+        )?;
+        if let Some(reason) = &self.sell_revert {
+            write!(f, " | Sell reverts: {}", reason)?;
+        }
+        if self.is_honeypot() {
+            write!(f, " | HONEYPOT")?;
+        }
+        Ok(())
+    }
+}
+
+/// A call of the simulated bundle.
+struct Call {
+    from: Address,
+    to: Address,
+    data: Bytes,
+    value: U256,
+}
+
+impl Call {
+    fn new(from: Address, to: Address, data: Option<Bytes>) -> Self {
+        Self {
+            from,
+            to,
+            data: data.unwrap_or_default(),
+            value: U256::zero(),
+        }
+    }
+
+    fn with_value(mut self, value: U256) -> Self {
+        self.value = value;
+        self
+    }
+}
+
+/// Result of a call of the simulated bundle: its return data, or its revert reason.
+type Outcome = std::result::Result<Bytes, String>;
+
+/// Buys `token` with `amount_in` of the wrapped native token through a UniswapV2 like
+/// router, transfers half of it, then sells a quarter back, all on top of the state
+/// of `block`. The taxes are what is missing compared to the router `getAmountsOut`.
+///
+/// The calls are chained in a single `eth_call`, the trader being a fake account given
+/// a balance and the code of a batch contract by a state override. With
+/// `SIMULATE_V1_<CHAIN>=true`, `eth_simulateV1` is tried first, the `eth_call` being
+/// the fallback when the node doesn't support it.
+pub async fn simulate_trade(
+    http: Arc<Provider<Http>>,
+    chain: &str,
+    router_address: Address,
+    native: Address,
+    token_address: Address,
+    amount_in: U256,
+    block: U64,
+) -> Result<Simulation> {
+    let trader: Address = TRADER.parse()?;
+    let recipient: Address = RECIPIENT.parse()?;
+    let router = UniV2Router::new(router_address, http.clone());
+    let token = SimulatedToken::new(token_address, http.clone());
+    let native_token = SimulatedToken::new(native, http.clone());
+    let buy_path = vec![native, token_address];
+    let sell_path = vec![token_address, native];
+
+    let buy = || {
+        Call::new(
+            trader,
+            router_address,
+            router
+                .swap_exact_eth_for_tokens_supporting_fee_on_transfer_tokens(
+                    U256::zero(),
+                    buy_path.clone(),
+                    trader,
+                    U256::MAX,
+                )
+                .calldata(),
+        )
+        .with_value(amount_in)
+    };
+
+    // The bought amount is needed to build the sell, so the buy is simulated first on its own.
+    let outcomes = simulate(
+        &http,
+        chain,
+        trader,
+        amount_in,
+        block,
+        vec![
+            Call::new(trader, router_address, router.get_amounts_out(amount_in, buy_path.clone()).calldata()),
+            buy(),
+            Call::new(trader, token_address, token.balance_of(trader).calldata()),
+        ],
+    )
+    .await?;
+    let expected_buy = last_amount(&outcomes[0])?;
+    if let Err(reason) = &outcomes[1] {
+        return Ok(Simulation {
+            buy_revert: Some(reason.clone()),
+            ..Default::default()
+        });
+    }
+    let bought = decode_u256(&outcomes[2])?;
+    let mut simulation = Simulation {
+        buy_tax: Some(tax(expected_buy, bought)),
+        ..Default::default()
+    };
+    if bought.is_zero() {
+        return Ok(simulation);
+    }
+
+    let sent = bought / 2;
+    let sold = bought / 4;
+    let outcomes = simulate(
+        &http,
+        chain,
+        trader,
+        amount_in,
+        block,
+        vec![
+            buy(),
+            Call::new(trader, token_address, token.transfer(recipient, sent).calldata()),
+            Call::new(trader, token_address, token.balance_of(recipient).calldata()),
+            Call::new(trader, token_address, token.approve(router_address, U256::MAX).calldata()),
+            Call::new(trader, router_address, router.get_amounts_out(sold, sell_path.clone()).calldata()),
+            Call::new(
+                trader,
+                router_address,
+                router
+                    .swap_exact_tokens_for_tokens_supporting_fee_on_transfer_tokens(
+                        sold,
+                        U256::zero(),
+                        sell_path,
+                        trader,
+                        U256::MAX,
+                    )
+                    .calldata(),
+            ),
+            Call::new(trader, native, native_token.balance_of(trader).calldata()),
+        ],
+    )
+    .await?;
+
+    if outcomes[1].is_ok() {
+        simulation.transfer_tax = Some(tax(sent, decode_u256(&outcomes[2])?));
+    }
+    match &outcomes[5] {
+        Ok(_) => {
+            let expected_sell = last_amount(&outcomes[4])?;
+            simulation.sell_tax = Some(tax(expected_sell, decode_u256(&outcomes[6])?));
+        }
+        Err(reason) => simulation.sell_revert = Some(reason.clone()),
+    }
+    Ok(simulation)
+}
+
+/// Runs the calls on top of `block`, the trader holding enough native token to pay
+/// for `amount_in`.
+async fn simulate(
+    http: &Provider<Http>,
+    chain: &str,
+    trader: Address,
+    amount_in: U256,
+    block: U64,
+    calls: Vec<Call>,
+) -> Result<Vec<Outcome>> {
+    let fast_path = std::env::var(format!("SIMULATE_V1_{}", chain))
+        .map(|value| value == "true")
+        .unwrap_or(false);
+    if fast_path {
+        if let Ok(outcomes) = simulate_v1(http, chain, trader, amount_in, block, &calls).await {
+            return Ok(outcomes);
+        }
+    }
+    batch_call(http, chain, trader, amount_in, block, &calls).await
+}
+
+/// Logs a simulation run on the latest state because `block` couldn't be read.
+fn log_latest_state(chain: &str, block: U64, err: &dyn fmt::Display) {
+    timestamp_print!(
+        Color::Yellow,
+        Some(false),
+        chain,
+        format!("[PAIR][SIM] State of block {} unavailable ({}), simulating on the latest state", block, err)
+    );
+}
+
+/// Runs the calls with one `eth_call` to the trader, whose code is overridden by
+/// `BATCH_CODE`, so each call is made with the trader as sender.
+async fn batch_call(
+    http: &Provider<Http>,
+    chain: &str,
+    trader: Address,
+    amount_in: U256,
+    block: U64,
+    calls: &[Call],
+) -> Result<Vec<Outcome>> {
+    let mut data = Vec::new();
+    for call in calls {
+        data.extend_from_slice(H256::from(call.to).as_bytes());
+        data.extend_from_slice(&<[u8; 32]>::from(call.value));
+        data.extend_from_slice(&<[u8; 32]>::from(U256::from(call.data.len())));
+        data.extend_from_slice(&call.data);
+        data.resize(data.len().next_multiple_of(32), 0);
+    }
+    let tx: TypedTransaction = TransactionRequest::new()
+        .from(trader)
+        .to(trader)
+        .data(data)
+        .into();
+    let mut state = spoof::state();
+    state
+        .account(trader)
+        .balance(amount_in * 2)
+        .code(BATCH_CODE.parse()?);
+
+    let output = match http.call_raw(&tx).block(block.into()).state(&state).await {
+        Ok(output) => output,
+        // Pruned nodes can't simulate on old blocks, the result then says how the
+        // token behaves now rather than at launch.
+        Err(err) => {
+            let output = http.call_raw(&tx).state(&state).await?;
+            log_latest_state(chain, block, &err);
+            output
+        }
+    };
+    decode_batch(&output, calls.len())
+}
+
+/// Outcomes of the calls returned by `BATCH_CODE`.
+fn decode_batch(output: &[u8], count: usize) -> Result<Vec<Outcome>> {
+    let word = |offset: usize| -> Result<U256> {
+        output
+            .get(offset..offset + 32)
+            .map(U256::from_big_endian)
+            .ok_or_else(|| eyre!("Truncated simulation output"))
+    };
+    let mut outcomes = Vec::with_capacity(count);
+    let mut offset = 0;
+    while offset < output.len() {
+        let success = !word(offset)?.is_zero();
+        let length = word(offset + 32)?;
+        if length > U256::from(output.len()) {
+            return Err(eyre!("Truncated simulation output"));
+        }
+        let length = length.as_usize();
+        let data = output
+            .get(offset + 64..offset + 64 + length)
+            .ok_or_else(|| eyre!("Truncated simulation output"))?;
+        outcomes.push(if success { Ok(Bytes::from(data.to_vec())) } else { Err(revert_reason(data)) });
+        offset += 64 + length.next_multiple_of(32);
+    }
+    if outcomes.len() != count {
+        return Err(eyre!("Expected {} simulated calls, got {}", count, outcomes.len()));
+    }
+    Ok(outcomes)
+}
+
+/// Message of an `Error(string)` revert, the raw data of a custom error.
+fn revert_reason(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "execution reverted".to_string();
+    }
+    if data.starts_with(&ERROR_SELECTOR) {
+        if let Ok(message) = String::decode(&data[4..]) {
+            return format!("execution reverted: {}", message);
+        }
+    }
+    format!("execution reverted: 0x{}", hex::encode(data))
+}
+
+/// Runs the calls in a single simulated block with `eth_simulateV1`.
+async fn simulate_v1(
+    http: &Provider<Http>,
+    chain: &str,
+    trader: Address,
+    amount_in: U256,
+    block: U64,
+    calls: &[Call],
+) -> Result<Vec<Outcome>> {
+    let count = calls.len();
+    let calls: Vec<Value> = calls
+        .iter()
+        .map(|call| {
+            json!({
+                "from": call.from,
+                "to": call.to,
+                "data": call.data,
+                "value": call.value,
+            })
+        })
+        .collect();
+    let payload = json!({
+        "blockStateCalls": [{
+            "stateOverrides": {
+                format!("{:#x}", trader): { "balance": amount_in * 2 }
+            },
+            "calls": calls,
+        }],
+        "validation": false,
+    });
+
+    let blocks: Value = match http
+        .request("eth_simulateV1", (payload.clone(), format!("{:#x}", block)))
+        .await
+    {
+        Ok(blocks) => blocks,
+        // Pruned nodes can't simulate on old blocks, see `batch_call`.
+        Err(err) => {
+            let blocks = http.request("eth_simulateV1", (payload, "latest")).await?;
+            log_latest_state(chain, block, &err);
+            blocks
+        }
+    };
+
+    let results = blocks[0]["calls"]
+        .as_array()
+        .filter(|results| results.len() == count)
+        .ok_or_else(|| eyre!("Unexpected eth_simulateV1 response: {}", blocks))?;
+    Ok(results
+        .iter()
+        .map(|result| {
+            if result["status"].as_str() == Some("0x1") {
+                let data = result["returnData"].as_str().unwrap_or("0x");
+                data.parse::<Bytes>().map_err(|err| err.to_string())
+            } else {
+                Err(result["error"]["message"]
+                    .as_str()
+                    .unwrap_or("execution reverted")
+                    .to_string())
+            }
+        })
+        .collect())
+}
+
+fn decode_u256(outcome: &Outcome) -> Result<U256> {
+    match outcome {
+        Ok(data) => Ok(U256::decode(data)?),
+        Err(reason) => Err(eyre!("{}", reason)),
+    }
+}
+
+/// Last amount of a `getAmountsOut` path, the one received.
+fn last_amount(outcome: &Outcome) -> Result<U256> {
+    match outcome {
+        Ok(data) => Vec::<U256>::decode(data)?
+            .last()
+            .copied()
+            .ok_or_else(|| eyre!("Empty getAmountsOut")),
+        Err(reason) => Err(eyre!("getAmountsOut failed: {}", reason)),
+    }
+}
+
+/// Share of `expected` that was not received.
+fn tax(expected: U256, received: U256) -> f64 {
+    if expected.is_zero() || received >= expected {
+        return 0.0;
+    }
+    u256_to_float(expected - received) / u256_to_float(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::abi::AbiEncode;
+
+    use super::*;
+
+    /// A call as `BATCH_CODE` returns it: success word, data length and padded data.
+    fn returned(success: bool, data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend(U256::from(success as u8).encode());
+        output.extend(U256::from(data.len()).encode());
+        output.extend_from_slice(data);
+        output.resize(output.len().next_multiple_of(32), 0);
+        output
+    }
+
+    fn error_string(message: &str) -> Vec<u8> {
+        [ERROR_SELECTOR.to_vec(), message.to_string().encode()].concat()
+    }
+
+    #[test]
+    fn decodes_the_batch_outcomes() {
+        let balance = U256::from(1_000_000u64).encode();
+        let output = [
+            returned(true, &balance),
+            returned(false, &error_string("Trading not enabled")),
+            returned(true, &[]),
+        ]
+        .concat();
+
+        let outcomes = decode_batch(&output, 3).unwrap();
+        assert_eq!(decode_u256(&outcomes[0]).unwrap(), U256::from(1_000_000u64));
+        assert_eq!(outcomes[1], Err("execution reverted: Trading not enabled".to_string()));
+        assert_eq!(outcomes[2], Ok(Bytes::default()));
+    }
+
+    #[test]
+    fn rejects_a_truncated_batch() {
+        let output = returned(true, &U256::from(7).encode());
+        assert_eq!(decode_batch(&output[..80], 1).unwrap_err().to_string(), "Truncated simulation output");
+        assert_eq!(decode_batch(&output[..40], 1).unwrap_err().to_string(), "Truncated simulation output");
+        // A length word larger than the output itself.
+        let mut output = output;
+        output[32..64].copy_from_slice(&U256::MAX.encode());
+        assert_eq!(decode_batch(&output, 1).unwrap_err().to_string(), "Truncated simulation output");
+        assert_eq!(
+            decode_batch(&returned(true, &[]), 2).unwrap_err().to_string(),
+            "Expected 2 simulated calls, got 1"
+        );
+    }
+
+    #[test]
+    fn reads_the_revert_reason() {
+        assert_eq!(revert_reason(&[]), "execution reverted");
+        assert_eq!(revert_reason(&error_string("TRANSFER_FAILED")), "execution reverted: TRANSFER_FAILED");
+        // A custom error, `TradingClosed()`, is kept raw.
+        assert_eq!(revert_reason(&[0x8a, 0x9b, 0x1c, 0x0d]), "execution reverted: 0x8a9b1c0d");
+        // An `Error(string)` whose message can't be decoded too.
+        assert_eq!(revert_reason(&ERROR_SELECTOR), "execution reverted: 0x08c379a0");
+    }
+}
@@ -0,0 +1,504 @@
+//! Persistent storage of what the tracker discovers (dexes, tokens, pairs,
+//! liquidity events and contract verifications) and of the alert deliveries.
+//!
+//! The SQLite backend is only built with the `sqlite` feature, without it
+//! `Store::open` fails so `--db` is rejected at startup.
+
+use ethers::prelude::*;
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::Store;
+
+#[cfg(not(feature = "sqlite"))]
+pub use disabled::Store;
+
+/// A pair or pool as stored in the `pairs` table.
+#[derive(Clone, Debug)]
+pub struct PairRecord {
+    pub chain: String,
+    pub factory: Address,
+    pub address: Address,
+    pub token0: Address,
+    pub token1: Address,
+    /// Fee tier of UniswapV3 like pools.
+    pub fee: Option<u32>,
+    pub tick_spacing: Option<i32>,
+    pub created_block: U64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiquidityKind {
+    Mint,
+    Burn,
+}
+
+impl LiquidityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LiquidityKind::Mint => "mint",
+            LiquidityKind::Burn => "burn",
+        }
+    }
+}
+
+/// A `Mint` or `Burn` as stored in the `liquidity_events` table.
+#[derive(Clone, Debug)]
+pub struct LiquidityRecord {
+    pub chain: String,
+    pub pair: Address,
+    pub kind: LiquidityKind,
+    pub sender: Option<Address>,
+    pub amount0: U256,
+    pub amount1: U256,
+    pub block_number: U64,
+    pub tx_hash: Option<H256>,
+    pub log_index: Option<U256>,
+}
+
+/// An alert as stored in the `alerts` table, waiting for its delivery to a sink.
+#[derive(Clone, Debug)]
+pub struct AlertRecord {
+    pub id: i64,
+    pub sink: String,
+    /// JSON of the `LiquidityEvent`.
+    pub payload: String,
+}
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::sync::Mutex;
+
+    use ethers::prelude::*;
+    use eyre::{Result, WrapErr};
+    use rusqlite::{params, Connection, OptionalExtension};
+
+    use super::{AlertRecord, LiquidityRecord, PairRecord};
+    use crate::dex::Dex;
+    use crate::token::Token;
+
+    /// Schema migrations, `PRAGMA user_version` holds how many were applied.
+    const MIGRATIONS: &[&str] = &[r#"
+        CREATE TABLE dexes (
+            chain TEXT NOT NULL,
+            factory TEXT NOT NULL,
+            router TEXT NOT NULL,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            PRIMARY KEY (chain, factory)
+        );
+        CREATE TABLE tokens (
+            chain TEXT NOT NULL,
+            address TEXT NOT NULL,
+            name TEXT NOT NULL,
+            symbol TEXT NOT NULL,
+            decimals INTEGER NOT NULL,
+            PRIMARY KEY (chain, address)
+        );
+        CREATE TABLE pairs (
+            chain TEXT NOT NULL,
+            address TEXT NOT NULL,
+            factory TEXT NOT NULL,
+            token0 TEXT NOT NULL,
+            token1 TEXT NOT NULL,
+            fee INTEGER,
+            tick_spacing INTEGER,
+            created_block INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (chain, address)
+        );
+        CREATE TABLE liquidity_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            chain TEXT NOT NULL,
+            pair TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            sender TEXT,
+            amount0 TEXT NOT NULL,
+            amount1 TEXT NOT NULL,
+            block_number INTEGER NOT NULL,
+            tx_hash TEXT,
+            log_index INTEGER,
+            seen_at INTEGER NOT NULL,
+            UNIQUE (chain, tx_hash, log_index)
+        );
+        CREATE INDEX liquidity_events_pair ON liquidity_events (chain, pair);
+        CREATE TABLE verifications (
+            chain TEXT NOT NULL,
+            token TEXT NOT NULL,
+            verified INTEGER NOT NULL,
+            checked_at INTEGER NOT NULL,
+            PRIMARY KEY (chain, token)
+        );
+    "#, r#"
+        ALTER TABLE pairs ADD COLUMN deployer TEXT;
+        ALTER TABLE pairs ADD COLUMN rugged INTEGER NOT NULL DEFAULT 0;
+        CREATE INDEX pairs_deployer ON pairs (chain, deployer);
+    "#, r#"
+        CREATE TABLE alerts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            chain TEXT NOT NULL,
+            sink TEXT NOT NULL,
+            key TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            queued_at INTEGER NOT NULL,
+            delivered_at INTEGER,
+            UNIQUE (chain, sink, key)
+        );
+    "#];
+
+    fn hex(address: Address) -> String {
+        format!("{:#x}", address)
+    }
+
+    pub struct Store {
+        conn: Mutex<Connection>,
+    }
+
+    impl Store {
+        pub fn open(path: &str) -> Result<Self> {
+            let mut conn =
+                Connection::open(path).wrap_err_with(|| format!("Can't open database {}", path))?;
+            Self::migrate(&mut conn)?;
+            Ok(Self {
+                conn: Mutex::new(conn),
+            })
+        }
+
+        fn migrate(conn: &mut Connection) -> Result<()> {
+            let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+            for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+                let tx = conn.transaction()?;
+                tx.execute_batch(migration)
+                    .wrap_err_with(|| format!("Database migration {} failed", index + 1))?;
+                tx.pragma_update(None, "user_version", index + 1)?;
+                tx.commit()?;
+            }
+            Ok(())
+        }
+
+        pub fn save_dex(&self, dex: &Dex) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "INSERT INTO dexes (chain, factory, router, name, kind) VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (chain, factory) DO UPDATE SET router = ?3, name = ?4, kind = ?5",
+                params![
+                    dex.chain,
+                    hex(dex.factory_address),
+                    hex(dex.router_address),
+                    dex.name,
+                    dex.kind.to_string()
+                ],
+            )?;
+            Ok(())
+        }
+
+        pub fn save_token(&self, chain: &str, token: &Token) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "INSERT OR IGNORE INTO tokens (chain, address, name, symbol, decimals) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![chain, hex(token.address), token.name, token.symbol, token.decimals],
+            )?;
+            Ok(())
+        }
+
+        pub fn save_pair(&self, pair: &PairRecord) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "INSERT OR IGNORE INTO pairs (chain, address, factory, token0, token1, fee, tick_spacing, created_block, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    pair.chain,
+                    hex(pair.address),
+                    hex(pair.factory),
+                    hex(pair.token0),
+                    hex(pair.token1),
+                    pair.fee,
+                    pair.tick_spacing,
+                    pair.created_block.as_u64(),
+                    chrono::Utc::now().timestamp()
+                ],
+            )?;
+            Ok(())
+        }
+
+        pub fn save_liquidity_event(&self, event: &LiquidityRecord) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "INSERT OR IGNORE INTO liquidity_events (chain, pair, kind, sender, amount0, amount1, block_number, tx_hash, log_index, seen_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![
+                    event.chain,
+                    hex(event.pair),
+                    event.kind.as_str(),
+                    event.sender.map(hex),
+                    event.amount0.to_string(),
+                    event.amount1.to_string(),
+                    event.block_number.as_u64(),
+                    event.tx_hash.map(|hash| format!("{:#x}", hash)),
+                    event.log_index.map(|index| index.as_u64()),
+                    chrono::Utc::now().timestamp()
+                ],
+            )?;
+            Ok(())
+        }
+
+        pub fn save_verification(&self, chain: &str, token: Address, verified: bool) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "INSERT INTO verifications (chain, token, verified, checked_at) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (chain, token) DO UPDATE SET verified = ?3, checked_at = ?4",
+                params![chain, hex(token), verified, chrono::Utc::now().timestamp()],
+            )?;
+            Ok(())
+        }
+
+        /// Wallet that added the first liquidity of the pair.
+        pub fn save_deployer(&self, chain: &str, pair: Address, deployer: Address) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "UPDATE pairs SET deployer = ?3 WHERE chain = ?1 AND address = ?2",
+                params![chain, hex(pair), hex(deployer)],
+            )?;
+            Ok(())
+        }
+
+        pub fn mark_rugged(&self, chain: &str, pair: Address) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "UPDATE pairs SET rugged = 1 WHERE chain = ?1 AND address = ?2",
+                params![chain, hex(pair)],
+            )?;
+            Ok(())
+        }
+
+        /// How many other pairs the wallet launched, and how many of them were rugged.
+        pub fn deployer_history(&self, chain: &str, deployer: Address, exclude: Address) -> Result<(u32, u32)> {
+            let history = self.conn.lock().unwrap().query_row(
+                "SELECT COUNT(*), COALESCE(SUM(rugged), 0) FROM pairs WHERE chain = ?1 AND deployer = ?2 AND address != ?3",
+                params![chain, hex(deployer), hex(exclude)],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            Ok(history)
+        }
+
+        /// Pairs of a factory that never received liquidity, to resume watching them after a restart.
+        pub fn pending_pairs(&self, chain: &str, factory: Address) -> Result<Vec<PairRecord>> {
+            let conn = self.conn.lock().unwrap();
+            let mut statement = conn.prepare(
+                "SELECT address, token0, token1, fee, tick_spacing, created_block FROM pairs p
+                 WHERE chain = ?1 AND factory = ?2 AND NOT EXISTS (
+                     SELECT 1 FROM liquidity_events l WHERE l.chain = p.chain AND l.pair = p.address AND l.kind = 'mint'
+                 )",
+            )?;
+            let rows = statement.query_map(params![chain, hex(factory)], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<u32>>(3)?,
+                    row.get::<_, Option<i32>>(4)?,
+                    row.get::<_, u64>(5)?,
+                ))
+            })?;
+
+            let mut pairs = Vec::new();
+            for row in rows {
+                let (address, token0, token1, fee, tick_spacing, created_block) = row?;
+                pairs.push(PairRecord {
+                    chain: chain.to_string(),
+                    factory,
+                    address: address.parse()?,
+                    token0: token0.parse()?,
+                    token1: token1.parse()?,
+                    fee,
+                    tick_spacing,
+                    created_block: created_block.into(),
+                });
+            }
+            Ok(pairs)
+        }
+
+        /// Queues an alert for a sink, `None` when the same alert (`key`) was already queued.
+        pub fn queue_alert(&self, chain: &str, sink: &str, key: &str, payload: &str) -> Result<Option<i64>> {
+            let conn = self.conn.lock().unwrap();
+            let inserted = conn.execute(
+                "INSERT OR IGNORE INTO alerts (chain, sink, key, payload, queued_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![chain, sink, key, payload, chrono::Utc::now().timestamp()],
+            )?;
+            Ok((inserted > 0).then(|| conn.last_insert_rowid()))
+        }
+
+        /// Marks an alert as done, delivered or rejected by its sink.
+        pub fn alert_delivered(&self, id: i64) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "UPDATE alerts SET delivered_at = ?2 WHERE id = ?1",
+                params![id, chrono::Utc::now().timestamp()],
+            )?;
+            Ok(())
+        }
+
+        /// Alerts of the chain that were never delivered, oldest first.
+        pub fn pending_alerts(&self, chain: &str) -> Result<Vec<AlertRecord>> {
+            let conn = self.conn.lock().unwrap();
+            let mut statement = conn.prepare(
+                "SELECT id, sink, payload FROM alerts WHERE chain = ?1 AND delivered_at IS NULL ORDER BY id",
+            )?;
+            let rows = statement.query_map(params![chain], |row| {
+                Ok(AlertRecord {
+                    id: row.get(0)?,
+                    sink: row.get(1)?,
+                    payload: row.get(2)?,
+                })
+            })?;
+            Ok(rows.collect::<rusqlite::Result<Vec<AlertRecord>>>()?)
+        }
+
+        /// Forgets a pair whose creation was reorged out, with its liquidity events and alerts.
+        pub fn delete_pair(&self, chain: &str, address: Address) -> Result<()> {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "DELETE FROM liquidity_events WHERE chain = ?1 AND pair = ?2",
+                params![chain, hex(address)],
+            )?;
+            conn.execute("DELETE FROM pairs WHERE chain = ?1 AND address = ?2", params![chain, hex(address)])?;
+            conn.execute(
+                "DELETE FROM alerts WHERE chain = ?1 AND key LIKE ?2",
+                params![chain, format!("{}:%", hex(address))],
+            )?;
+            Ok(())
+        }
+
+        /// Forgets the deployer and the rug of a pair whose first liquidity was reorged out.
+        pub fn reset_launch(&self, chain: &str, pair: Address) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "UPDATE pairs SET deployer = NULL, rugged = 0 WHERE chain = ?1 AND address = ?2",
+                params![chain, hex(pair)],
+            )?;
+            Ok(())
+        }
+
+        /// Forgets the rug of a pair whose liquidity removal was reorged out.
+        pub fn unmark_rugged(&self, chain: &str, pair: Address) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "UPDATE pairs SET rugged = 0 WHERE chain = ?1 AND address = ?2",
+                params![chain, hex(pair)],
+            )?;
+            Ok(())
+        }
+
+        /// Forgets a liquidity event whose log was reorged out.
+        pub fn delete_liquidity_event(&self, chain: &str, tx_hash: H256, log_index: U256) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "DELETE FROM liquidity_events WHERE chain = ?1 AND tx_hash = ?2 AND log_index = ?3",
+                params![chain, format!("{:#x}", tx_hash), log_index.as_u64()],
+            )?;
+            Ok(())
+        }
+
+        /// Forgets an alert for every sink, so it is sent again if its event comes back.
+        pub fn forget_alert(&self, chain: &str, key: &str) -> Result<()> {
+            self.conn.lock().unwrap().execute(
+                "DELETE FROM alerts WHERE chain = ?1 AND key = ?2",
+                params![chain, key],
+            )?;
+            Ok(())
+        }
+
+        pub fn is_known_pair(&self, chain: &str, address: Address) -> Result<bool> {
+            let found = self
+                .conn
+                .lock()
+                .unwrap()
+                .query_row(
+                    "SELECT 1 FROM pairs WHERE chain = ?1 AND address = ?2",
+                    params![chain, hex(address)],
+                    |_| Ok(()),
+                )
+                .optional()?;
+            Ok(found.is_some())
+        }
+    }
+}
+
+#[cfg(not(feature = "sqlite"))]
+mod disabled {
+    use ethers::prelude::*;
+    use eyre::{eyre, Result};
+
+    use super::{AlertRecord, LiquidityRecord, PairRecord};
+    use crate::dex::Dex;
+    use crate::token::Token;
+
+    /// Placeholder used when the crate is built without the `sqlite` feature.
+    pub struct Store;
+
+    impl Store {
+        pub fn open(_path: &str) -> Result<Self> {
+            Err(eyre!("--db requires building with the `sqlite` feature"))
+        }
+
+        pub fn save_dex(&self, _dex: &Dex) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn save_token(&self, _chain: &str, _token: &Token) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn save_pair(&self, _pair: &PairRecord) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn save_liquidity_event(&self, _event: &LiquidityRecord) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn save_verification(&self, _chain: &str, _token: Address, _verified: bool) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn save_deployer(&self, _chain: &str, _pair: Address, _deployer: Address) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn mark_rugged(&self, _chain: &str, _pair: Address) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn reset_launch(&self, _chain: &str, _pair: Address) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn unmark_rugged(&self, _chain: &str, _pair: Address) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn deployer_history(&self, _chain: &str, _deployer: Address, _exclude: Address) -> Result<(u32, u32)> {
+            Ok((0, 0))
+        }
+
+        pub fn pending_pairs(&self, _chain: &str, _factory: Address) -> Result<Vec<PairRecord>> {
+            Ok(Vec::new())
+        }
+
+        pub fn queue_alert(&self, _chain: &str, _sink: &str, _key: &str, _payload: &str) -> Result<Option<i64>> {
+            Ok(None)
+        }
+
+        pub fn alert_delivered(&self, _id: i64) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn pending_alerts(&self, _chain: &str) -> Result<Vec<AlertRecord>> {
+            Ok(Vec::new())
+        }
+
+        pub fn delete_pair(&self, _chain: &str, _address: Address) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn delete_liquidity_event(&self, _chain: &str, _tx_hash: H256, _log_index: U256) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn forget_alert(&self, _chain: &str, _key: &str) -> Result<()> {
+            Ok(())
+        }
+
+        pub fn is_known_pair(&self, _chain: &str, _address: Address) -> Result<bool> {
+            Ok(false)
+        }
+    }
+}
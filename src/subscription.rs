@@ -0,0 +1,654 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ethers::prelude::*;
+use tokio::{
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+    time::timeout,
+};
+
+use crate::backfill::LogPager;
+use crate::block_scanner::{BlockHeader, BlockTracker};
+use crate::timestamp_print;
+use colored::*;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// How often the WebSocket connection is checked when no log comes in.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Recent blocks whose hash is kept to catch reorgs, deeper ones go unnoticed.
+const REORG_WINDOW: u64 = 64;
+/// Retractions kept for a receiver that falls behind.
+const RETRACTIONS_CAPACITY: usize = 256;
+
+/// Progress of a subscription, used to backfill what was missed during an outage.
+#[derive(Default)]
+struct Progress {
+    /// Last block a log was delivered for (or the head when the subscription started).
+    last_block: Option<U64>,
+    /// Logs already delivered for `last_block`, by block hash, transaction and index.
+    seen: HashSet<(Option<H256>, Option<H256>, Option<U256>)>,
+}
+
+/// Hashes of the recent canonical blocks, from the block headers.
+#[derive(Default)]
+struct Canonical(BTreeMap<u64, H256>);
+
+impl Canonical {
+    /// A header replaces the blocks from its height on, which were reorged out if any.
+    fn insert(&mut self, header: &BlockHeader) {
+        let number = header.number.as_u64();
+        self.0.split_off(&number);
+        self.0.insert(number, header.hash);
+        while self.0.len() as u64 > REORG_WINDOW {
+            self.0.pop_first();
+        }
+    }
+
+    fn head(&self) -> Option<u64> {
+        self.0.keys().next_back().copied()
+    }
+
+    /// False when the block of the log was replaced, true when it is unknown.
+    fn contains(&self, log: &Log) -> bool {
+        match (log.block_number, log.block_hash) {
+            (Some(number), Some(hash)) => match self.0.get(&number.as_u64()) {
+                Some(canonical) => *canonical == hash,
+                None => true,
+            },
+            _ => true,
+        }
+    }
+}
+
+impl std::fmt::Debug for Canonical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Canonical").field("head", &self.head()).finish()
+    }
+}
+
+/// Logs of a subscription around the head.
+#[derive(Default)]
+struct Confirmation {
+    /// Waiting for their confirmations.
+    pending: Vec<Log>,
+    /// Delivered within the reorg window, retracted if their block is reorged out.
+    delivered: Vec<Log>,
+}
+
+fn same_log(a: &Log, b: &Log) -> bool {
+    a.block_hash == b.block_hash && a.transaction_hash == b.transaction_hash && a.log_index == b.log_index
+}
+
+#[derive(Clone)]
+struct Subscription {
+    /// Filter without block range, the range is only set for the `eth_getLogs` backfills.
+    filter: Filter,
+    from_block: Option<U64>,
+    progress: Arc<Mutex<Progress>>,
+    tx: mpsc::UnboundedSender<Log>,
+    /// Blocks on top of a log before it is delivered.
+    confirmations: u64,
+    confirmation: Arc<Mutex<Confirmation>>,
+    canonical: Arc<Mutex<Canonical>>,
+    retractions: broadcast::Sender<Log>,
+}
+
+impl Subscription {
+    /// First block that still has to be fetched with `eth_getLogs`.
+    fn backfill_from(&self) -> Option<U64> {
+        let progress = self.progress.lock().unwrap();
+        match progress.last_block {
+            Some(block) => Some(block),
+            None => self.from_block,
+        }
+    }
+
+    /// Takes a log unless it was already delivered, it is forwarded once confirmed.
+    /// A removed log is retracted. Returns false once the receiver is gone.
+    fn deliver(&self, log: Log) -> bool {
+        if log.removed == Some(true) {
+            self.retract(&log);
+            return !self.tx.is_closed();
+        }
+        {
+            let mut progress = self.progress.lock().unwrap();
+            let block = log.block_number;
+            let key = (log.block_hash, log.transaction_hash, log.log_index);
+            match (block, progress.last_block) {
+                (Some(block), Some(last)) if block < last => return true,
+                (Some(block), Some(last)) if block == last => {
+                    if !progress.seen.insert(key) {
+                        return true;
+                    }
+                }
+                (Some(block), _) => {
+                    progress.last_block = Some(block);
+                    progress.seen.clear();
+                    progress.seen.insert(key);
+                }
+                (None, _) => {}
+            }
+        }
+        self.confirmation.lock().unwrap().pending.push(log);
+        let canonical = self.canonical.lock().unwrap();
+        self.on_head(&canonical)
+    }
+
+    /// Once the receiver is gone, the subscription is only kept to retract the logs
+    /// it delivered, until they leave the reorg window.
+    fn is_done(&self) -> bool {
+        self.tx.is_closed() && self.confirmation.lock().unwrap().delivered.is_empty()
+    }
+
+    /// Forwards the pending logs with enough blocks on top of them, drops the ones
+    /// whose block was reorged out and retracts the delivered ones that were.
+    /// Returns false once the receiver is gone.
+    fn on_head(&self, canonical: &Canonical) -> bool {
+        let mut confirmation = self.confirmation.lock().unwrap();
+        let head = canonical.head();
+        let (ready, waiting): (Vec<Log>, Vec<Log>) = confirmation
+            .pending
+            .drain(..)
+            .filter(|log| canonical.contains(log))
+            .partition(|log| match (self.confirmations, log.block_number, head) {
+                (0, _, _) | (_, None, _) => true,
+                (confirmations, Some(block), Some(head)) => block.as_u64() + confirmations <= head,
+                (_, Some(_), None) => false,
+            });
+        confirmation.pending = waiting;
+
+        let (kept, reorged): (Vec<Log>, Vec<Log>) =
+            confirmation.delivered.drain(..).partition(|log| canonical.contains(log));
+        for mut log in reorged {
+            log.removed = Some(true);
+            self.rewind(&log);
+            let _ = self.retractions.send(log);
+        }
+        confirmation.delivered = kept;
+        if let Some(head) = head {
+            confirmation
+                .delivered
+                .retain(|log| log.block_number.is_some_and(|block| block.as_u64() + REORG_WINDOW > head));
+        }
+
+        for log in ready {
+            if self.tx.send(log.clone()).is_err() {
+                return false;
+            }
+            confirmation.delivered.push(log);
+        }
+        !self.tx.is_closed()
+    }
+
+    /// A log removed by the node: dropped if still pending, retracted if delivered.
+    fn retract(&self, removed: &Log) {
+        let mut confirmation = self.confirmation.lock().unwrap();
+        confirmation.pending.retain(|log| !same_log(log, removed));
+        if let Some(index) = confirmation.delivered.iter().position(|log| same_log(log, removed)) {
+            let log = confirmation.delivered.remove(index);
+            self.rewind(&log);
+            let _ = self.retractions.send(removed.clone());
+        }
+    }
+
+    /// Lets the log through again if it is mined in another block.
+    fn rewind(&self, log: &Log) {
+        let mut progress = self.progress.lock().unwrap();
+        if let (Some(block), Some(last)) = (log.block_number, progress.last_block) {
+            if block <= last {
+                progress.last_block = Some(block.saturating_sub(U64::one()));
+                progress.seen.clear();
+            }
+        }
+    }
+
+    /// Marks the subscription as live from `block` if nothing was delivered yet.
+    fn start_at(&self, block: U64) {
+        let mut progress = self.progress.lock().unwrap();
+        if progress.last_block.is_none() && self.from_block.is_none() {
+            progress.last_block = Some(block);
+        }
+    }
+}
+
+/// Log subscriptions of a chain multiplexed on a single WebSocket connection.
+///
+/// The connection is supervised: when it drops (or stops answering) it is
+/// re-established with an exponential backoff, every subscription is renewed
+/// and the blocks missed in between are fetched with `eth_getLogs` from the
+/// last block processed, so no log is lost across an outage.
+///
+/// Logs are held until `confirmations` blocks are mined on top of theirs, and
+/// dropped if their block is reorged out meanwhile. A log delivered in a block
+/// that is later reorged out is sent to the `retractions` with `removed` set.
+///
+/// The connection, and the block tracking, only start with the first subscription.
+#[derive(Clone, Debug)]
+pub struct LogSubscriber {
+    commands: mpsc::UnboundedSender<Subscription>,
+    confirmations: u64,
+    canonical: Arc<Mutex<Canonical>>,
+    retractions: broadcast::Sender<Log>,
+    /// What the supervisor needs, until it starts.
+    idle: Arc<Mutex<Option<Supervisor>>>,
+}
+
+#[derive(Debug)]
+struct Supervisor {
+    chain: String,
+    ws_url: String,
+    http: Arc<Provider<Http>>,
+    commands: mpsc::UnboundedReceiver<Subscription>,
+    blocks: BlockTracker,
+}
+
+impl LogSubscriber {
+    pub fn new(chain: String, ws_url: String, http: Arc<Provider<Http>>, blocks: &BlockTracker, confirmations: u64) -> Self {
+        let (commands, rx) = mpsc::unbounded_channel();
+        let canonical = Arc::new(Mutex::new(Canonical::default()));
+        let (retractions, _) = broadcast::channel(RETRACTIONS_CAPACITY);
+        let supervisor = Supervisor {
+            chain,
+            ws_url,
+            http,
+            commands: rx,
+            blocks: blocks.clone(),
+        };
+        Self {
+            commands,
+            confirmations,
+            canonical,
+            retractions,
+            idle: Arc::new(Mutex::new(Some(supervisor))),
+        }
+    }
+
+    /// Logs delivered by any subscription whose block was reorged out, `removed` set.
+    pub fn retractions(&self) -> broadcast::Receiver<Log> {
+        self.retractions.subscribe()
+    }
+
+    /// Subscribes to the logs matching `filter`. When `from_block` is set, the logs
+    /// since that block are fetched first. Dropping the receiver ends the subscription.
+    pub fn subscribe(&self, filter: Filter, from_block: Option<U64>) -> mpsc::UnboundedReceiver<Log> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let subscription = Subscription {
+            filter,
+            from_block,
+            progress: Arc::new(Mutex::new(Progress::default())),
+            tx,
+            confirmations: self.confirmations,
+            confirmation: Arc::new(Mutex::new(Confirmation::default())),
+            canonical: self.canonical.clone(),
+            retractions: self.retractions.clone(),
+        };
+        let _ = self.commands.send(subscription);
+        if let Some(supervisor) = self.idle.lock().unwrap().take() {
+            let headers = supervisor.blocks.subscribe();
+            tokio::spawn(supervise(
+                supervisor.chain,
+                supervisor.ws_url,
+                supervisor.http,
+                supervisor.commands,
+                headers,
+                self.canonical.clone(),
+            ));
+        }
+        rx
+    }
+}
+
+async fn supervise(
+    chain: String,
+    ws_url: String,
+    http: Arc<Provider<Http>>,
+    mut commands: mpsc::UnboundedReceiver<Subscription>,
+    mut headers: broadcast::Receiver<BlockHeader>,
+    canonical: Arc<Mutex<Canonical>>,
+) {
+    let mut subscriptions: Vec<Subscription> = Vec::new();
+    let mut backoff = MIN_BACKOFF;
+    let mut connected_once = false;
+
+    loop {
+        let ws = match Provider::<Ws>::connect(ws_url.clone()).await {
+            Ok(ws) => Arc::new(ws),
+            Err(err) => {
+                timestamp_print!(
+                    Color::Red,
+                    Some(false),
+                    chain,
+                    format!("[WSS] Connection failed: {}, retrying in {}s", err, backoff.as_secs())
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+        if connected_once {
+            timestamp_print!(Color::Green, Some(false), chain, format!("[WSS] Reconnected, resuming {} subscriptions", subscriptions.len()));
+        }
+        connected_once = true;
+        backoff = MIN_BACKOFF;
+
+        let (ended_tx, mut ended_rx) = mpsc::unbounded_channel::<()>();
+        subscriptions.retain(|subscription| !subscription.is_done());
+        let mut tasks: Vec<JoinHandle<()>> = subscriptions
+            .iter()
+            .filter(|subscription| !subscription.tx.is_closed())
+            .map(|subscription| {
+                tokio::spawn(run_subscription(
+                    chain.clone(),
+                    ws.clone(),
+                    http.clone(),
+                    subscription.clone(),
+                    ended_tx.clone(),
+                ))
+            })
+            .collect();
+
+        let mut heartbeat = tokio::time::interval(HEARTBEAT);
+        loop {
+            tokio::select! {
+                command = commands.recv() => match command {
+                    Some(subscription) => {
+                        tasks.retain(|task| !task.is_finished());
+                        subscriptions.retain(|subscription| !subscription.is_done());
+                        tasks.push(tokio::spawn(run_subscription(
+                            chain.clone(),
+                            ws.clone(),
+                            http.clone(),
+                            subscription.clone(),
+                            ended_tx.clone(),
+                        )));
+                        subscriptions.push(subscription);
+                    }
+                    None => return,
+                },
+                _ = ended_rx.recv() => break,
+                header = headers.recv() => match header {
+                    Ok(header) => {
+                        let mut canonical = canonical.lock().unwrap();
+                        canonical.insert(&header);
+                        for subscription in &subscriptions {
+                            subscription.on_head(&canonical);
+                        }
+                        subscriptions.retain(|subscription| !subscription.is_done());
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    // The block tracker runs as long as the process.
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = heartbeat.tick() => {
+                    if !matches!(timeout(HEARTBEAT_TIMEOUT, ws.get_block_number()).await, Ok(Ok(_))) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        for task in tasks {
+            task.abort();
+        }
+        timestamp_print!(
+            Color::Red,
+            Some(false),
+            chain,
+            format!("[WSS] Connection lost, reconnecting in {}s", backoff.as_secs())
+        );
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+/// Runs a subscription on the current connection, `ended` is notified if the stream stops.
+async fn run_subscription(
+    chain: String,
+    ws: Arc<Provider<Ws>>,
+    http: Arc<Provider<Http>>,
+    subscription: Subscription,
+    ended: mpsc::UnboundedSender<()>,
+) {
+    // Subscribe before the backfill so nothing falls in between, duplicates are dropped.
+    let mut stream = match ws.subscribe_logs(&subscription.filter).await {
+        Ok(stream) => stream,
+        Err(_) => {
+            let _ = ended.send(());
+            return;
+        }
+    };
+
+    if let Some(from_block) = subscription.backfill_from() {
+        if !catch_up(&chain, &http, &subscription, from_block).await {
+            return;
+        }
+    } else if let Ok(block) = http.get_block_number().await {
+        subscription.start_at(block);
+    }
+
+    while let Some(log) = stream.next().await {
+        if !subscription.deliver(log) {
+            return;
+        }
+    }
+    let _ = ended.send(());
+}
+
+/// Delivers the logs from `from_block` up to the head, paged to stay within the node
+/// limits. A failed request is retried with an exponential backoff, the subscription
+/// only goes live once caught up. Returns false once the receiver is gone.
+async fn catch_up(chain: &str, http: &Arc<Provider<Http>>, subscription: &Subscription, from_block: U64) -> bool {
+    let mut backoff = MIN_BACKOFF;
+    let head = loop {
+        match http.get_block_number().await {
+            Ok(head) => break head,
+            Err(err) => {
+                if !wait_retry(chain, from_block, &err.to_string(), &mut backoff, subscription).await {
+                    return false;
+                }
+            }
+        }
+    };
+    let mut pager = LogPager::new(http.clone(), subscription.filter.clone(), from_block.as_u64(), head.as_u64());
+    loop {
+        match pager.next_page().await {
+            Ok(Some(logs)) => {
+                backoff = MIN_BACKOFF;
+                for log in logs {
+                    if !subscription.deliver(log) {
+                        return false;
+                    }
+                }
+            }
+            Ok(None) => return true,
+            Err(err) => {
+                let from_block = U64::from(pager.progress() + 1);
+                if !wait_retry(chain, from_block, &err.to_string(), &mut backoff, subscription).await {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+/// Waits before the next catch-up attempt, false if the receiver is gone meanwhile.
+async fn wait_retry(chain: &str, from_block: U64, err: &str, backoff: &mut Duration, subscription: &Subscription) -> bool {
+    timestamp_print!(
+        Color::Red,
+        Some(false),
+        chain,
+        format!("[WSS] Backfill from block {} failed: {}, retrying in {}s", from_block, err, backoff.as_secs())
+    );
+    tokio::time::sleep(*backoff).await;
+    *backoff = (*backoff * 2).min(MAX_BACKOFF);
+    !subscription.tx.is_closed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(confirmations: u64) -> (Subscription, mpsc::UnboundedReceiver<Log>, broadcast::Receiver<Log>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (retractions, retracted) = broadcast::channel(RETRACTIONS_CAPACITY);
+        let subscription = Subscription {
+            filter: Filter::new(),
+            from_block: None,
+            progress: Arc::new(Mutex::new(Progress::default())),
+            tx,
+            confirmations,
+            confirmation: Arc::new(Mutex::new(Confirmation::default())),
+            canonical: Arc::new(Mutex::new(Canonical::default())),
+            retractions,
+        };
+        (subscription, rx, retracted)
+    }
+
+    /// Hash of block `number`, `fork` tells apart the blocks competing for a height.
+    fn hash(number: u64, fork: u64) -> H256 {
+        H256::from_low_u64_be((number << 8) | fork)
+    }
+
+    fn log(number: u64, fork: u64, index: u64) -> Log {
+        Log {
+            block_number: Some(number.into()),
+            block_hash: Some(hash(number, fork)),
+            transaction_hash: Some(H256::from_low_u64_be(index + 1)),
+            log_index: Some(index.into()),
+            ..Default::default()
+        }
+    }
+
+    /// A new head, as the block tracker hands it to the supervisor.
+    fn mine(subscription: &Subscription, number: u64, fork: u64) -> bool {
+        let mut canonical = subscription.canonical.lock().unwrap();
+        canonical.insert(&BlockHeader {
+            number: number.into(),
+            hash: hash(number, fork),
+            parent_hash: hash(number.saturating_sub(1), 0),
+            timestamp: U256::zero(),
+            base_fee: None,
+        });
+        subscription.on_head(&canonical)
+    }
+
+    #[test]
+    fn drops_the_logs_already_delivered() {
+        let (subscription, mut rx, _) = subscription(0);
+        subscription.deliver(log(10, 0, 0));
+        subscription.deliver(log(10, 0, 1));
+        // Delivered again by the backfill after a reconnection.
+        subscription.deliver(log(10, 0, 0));
+        assert_eq!(rx.try_recv().unwrap(), log(10, 0, 0));
+        assert_eq!(rx.try_recv().unwrap(), log(10, 0, 1));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn drops_the_logs_of_earlier_blocks() {
+        let (subscription, mut rx, _) = subscription(0);
+        subscription.deliver(log(11, 0, 0));
+        subscription.deliver(log(10, 0, 1));
+        assert_eq!(rx.try_recv().unwrap(), log(11, 0, 0));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(subscription.backfill_from(), Some(U64::from(11)));
+    }
+
+    #[test]
+    fn starts_over_on_a_new_block() {
+        let (subscription, mut rx, _) = subscription(0);
+        subscription.deliver(log(10, 0, 0));
+        subscription.deliver(log(11, 0, 0));
+        assert_eq!(subscription.progress.lock().unwrap().seen.len(), 1);
+        // Same transaction and index, but in another block.
+        assert_eq!(rx.try_recv().unwrap(), log(10, 0, 0));
+        assert_eq!(rx.try_recv().unwrap(), log(11, 0, 0));
+        assert_eq!(subscription.backfill_from(), Some(U64::from(11)));
+    }
+
+    #[test]
+    fn holds_a_log_until_confirmed() {
+        let (subscription, mut rx, _) = subscription(2);
+        mine(&subscription, 10, 0);
+        assert!(subscription.deliver(log(10, 0, 0)));
+        mine(&subscription, 11, 0);
+        assert!(rx.try_recv().is_err());
+
+        mine(&subscription, 12, 0);
+        assert_eq!(rx.try_recv().unwrap(), log(10, 0, 0));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn drops_a_pending_log_of_a_replaced_block() {
+        let (subscription, mut rx, mut retracted) = subscription(2);
+        mine(&subscription, 10, 0);
+        subscription.deliver(log(10, 0, 0));
+        mine(&subscription, 10, 1);
+        mine(&subscription, 11, 1);
+        mine(&subscription, 12, 1);
+
+        assert!(rx.try_recv().is_err());
+        assert!(subscription.confirmation.lock().unwrap().pending.is_empty());
+        // It was never delivered, there is nothing to retract.
+        assert!(retracted.try_recv().is_err());
+    }
+
+    #[test]
+    fn retracts_the_logs_of_a_reorged_block() {
+        let (subscription, mut rx, mut retracted) = subscription(0);
+        mine(&subscription, 10, 0);
+        subscription.deliver(log(10, 0, 0));
+        assert_eq!(rx.try_recv().unwrap(), log(10, 0, 0));
+
+        mine(&subscription, 10, 1);
+        let removed = retracted.try_recv().unwrap();
+        assert_eq!(removed.removed, Some(true));
+        assert!(same_log(&removed, &log(10, 0, 0)));
+        assert!(subscription.confirmation.lock().unwrap().delivered.is_empty());
+    }
+
+    #[test]
+    fn lets_a_retracted_log_through_again() {
+        let (subscription, mut rx, mut retracted) = subscription(0);
+        mine(&subscription, 10, 0);
+        subscription.deliver(log(10, 0, 0));
+        mine(&subscription, 11, 0);
+        subscription.deliver(log(11, 0, 1));
+        assert_eq!(rx.try_recv().unwrap(), log(10, 0, 0));
+        assert_eq!(rx.try_recv().unwrap(), log(11, 0, 1));
+
+        // The node removes the log of block 10, which is then mined again in
+        // another block 10, behind the last block delivered.
+        let removed = Log { removed: Some(true), ..log(10, 0, 0) };
+        assert!(subscription.deliver(removed.clone()));
+        assert_eq!(retracted.try_recv().unwrap(), removed);
+        mine(&subscription, 10, 1);
+        subscription.deliver(log(10, 1, 0));
+        assert_eq!(rx.try_recv().unwrap(), log(10, 1, 0));
+    }
+
+    #[test]
+    fn keeps_the_reorg_window_only() {
+        let (subscription, _rx, _) = subscription(0);
+        mine(&subscription, 1, 0);
+        subscription.deliver(log(1, 0, 0));
+        for number in 2..=REORG_WINDOW {
+            mine(&subscription, number, 0);
+        }
+        assert_eq!(subscription.confirmation.lock().unwrap().delivered.len(), 1);
+
+        mine(&subscription, REORG_WINDOW + 1, 0);
+        assert!(subscription.confirmation.lock().unwrap().delivered.is_empty());
+        let canonical = subscription.canonical.lock().unwrap();
+        assert_eq!(canonical.0.len() as u64, REORG_WINDOW);
+        assert_eq!(canonical.0.keys().next().copied(), Some(2));
+        // A block out of the window is unknown, its logs are taken as canonical.
+        assert!(canonical.contains(&log(1, 1, 0)));
+    }
+}
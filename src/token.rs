@@ -1,10 +1,10 @@
 use std::sync::Arc;
 
 use ethers::prelude::*;
-use eyre::Result;
-
-use reqwest::Error;
+use eyre::{Result, WrapErr};
 
+use crate::risk::{analyze, RiskReport};
+use crate::simulation::Simulation;
 use crate::timestamp_print;
 use colored::*;
 
@@ -14,7 +14,12 @@ pub struct Token {
     pub name: String,
     pub symbol: String,
     pub decimals: u8,
-    pub is_verified: bool,
+    /// Whether the source code is verified on the explorer, `None` until checked.
+    pub verified: Option<bool>,
+    /// Buy / sell simulation run when liquidity is first added.
+    pub simulation: Option<Simulation>,
+    /// Bytecode and ownership risk signals.
+    pub risk: Option<RiskReport>,
 }
 
 impl Token {
@@ -24,34 +29,35 @@ impl Token {
         let name = ERC20::new(address, provider.clone())
             .name()
             .call()
-            .await
-            .unwrap();
+            .await?;
         let symbol = ERC20::new(address, provider.clone())
             .symbol()
             .call()
-            .await
-            .unwrap();
+            .await?;
         let decimals = ERC20::new(address, provider.clone())
             .decimals()
             .call()
-            .await
-            .unwrap();
+            .await?;
 
         Ok(Self {
             address,
             name,
             symbol,
             decimals,
-            is_verified: false,
+            verified: None,
+            simulation: None,
+            risk: None,
         })
     }
 
-    pub async fn verified_contract(&mut self, chain: String) -> Result<bool, Error> {
-        if self.is_verified {
-            return Ok(true);
+    /// Asks the chain explorer whether the token source code is verified. Fails when
+    /// `ETHERSCAN_API_KEY` is missing or the explorer can't be reached.
+    pub async fn verified_contract(&mut self, chain: String) -> Result<bool> {
+        if let Some(verified) = self.verified {
+            return Ok(verified);
         }
-        let explorer = Token::get_explorer(chain);
-        let api_key = std::env::var("ETHERSCAN_API_KEY").expect("missing API KEY");
+        let explorer = Token::get_explorer(chain.clone());
+        let api_key = std::env::var("ETHERSCAN_API_KEY").wrap_err("missing ETHERSCAN_API_KEY")?;
         let url = format!(
             "https://api.{}api?module=contract&action=getabi&address={:#066x}&apikey={}",
             explorer, self.address, api_key
@@ -71,13 +77,15 @@ impl Token {
         timestamp_print!(
             status,
             Some(false),
+            chain,
             format!("[TOKEN] {} | Contrat verified: {}", self.name, verified)
         );
+        self.verified = Some(verified);
         if verified {
-            self.is_verified = true;
             timestamp_print!(
                 Color::Blue,
                 Some(false),
+                chain,
                 format!(
                     "[TOKEN] Contract code: https://{}address/{:#066x}#code",
                     explorer, self.address
@@ -87,18 +95,41 @@ impl Token {
         Ok(verified)
     }
 
-    fn get_explorer(chain: String) -> String {
+    /// Scans the token contract for risk signals, the report is kept on the token.
+    pub async fn analyze_risk(&mut self, chain: &str, provider: Arc<Provider<Http>>) -> Result<&RiskReport> {
+        let report = analyze(provider, self.address).await?;
+        let status = match report.score {
+            0..=19 => Color::Green,
+            20..=49 => Color::Yellow,
+            _ => Color::Red,
+        };
+        timestamp_print!(
+            status,
+            Some(false),
+            chain,
+            format!("[TOKEN] {} | {}", self.name, report)
+        );
+        Ok(self.risk.insert(report))
+    }
+
+    /// Explorer of the chain, etherscan.io for a chain without its own.
+    pub(crate) fn get_explorer(chain: String) -> String {
+        Token::own_explorer(&chain).unwrap_or("etherscan.io/").to_string()
+    }
+
+    /// Explorer of the chain, `None` when it has none of its own.
+    pub(crate) fn own_explorer(chain: &str) -> Option<&'static str> {
         match chain.to_lowercase().as_str() {
-            "ethereum" => "etherscan.io/".to_string(),
-            "ropsten" => "ropsten.etherscan.io/".to_string(),
-            "rinkeby" => "rinkeby.etherscan.io/".to_string(),
-            "goerli" => "goerli.etherscan.io/".to_string(),
-            "kovan" => "kovan.etherscan.io/".to_string(),
-            "bsc" => "bscscan.com/".to_string(),
-            "arbitrum" => "arbiscan.io/".to_string(),
-            "polygon" => "polygonscan.com/".to_string(),
-            "avalanche" => "snowtrace.io/".to_string(),
-            _ => "etherscan.io/".to_string(),
+            "ethereum" => Some("etherscan.io/"),
+            "ropsten" => Some("ropsten.etherscan.io/"),
+            "rinkeby" => Some("rinkeby.etherscan.io/"),
+            "goerli" => Some("goerli.etherscan.io/"),
+            "kovan" => Some("kovan.etherscan.io/"),
+            "bsc" => Some("bscscan.com/"),
+            "arbitrum" => Some("arbiscan.io/"),
+            "polygon" => Some("polygonscan.com/"),
+            "avalanche" => Some("snowtrace.io/"),
+            _ => None,
         }
     }
 }
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::dex::Dex;
+use crate::helpers::u256_amount;
+use crate::token::Token;
+
+/// Profile of the wallet that added the first liquidity of a pair, usually the
+/// one that deployed the token.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WalletProfile {
+    pub address: Address,
+    /// Block of the first transaction sent by the wallet.
+    pub first_block: Option<U64>,
+    /// Days between the first transaction and the block of the profile.
+    pub age_days: Option<f64>,
+    /// Native token balance.
+    pub balance: Option<f64>,
+    /// Sender of the first transaction received by the wallet.
+    pub funder: Option<Address>,
+    /// Other pairs that got their first liquidity from this wallet, according to the store.
+    pub launches: Option<u32>,
+    /// How many of them were rugged.
+    pub rugs: Option<u32>,
+}
+
+impl WalletProfile {
+    /// Profiles `wallet` at `block`, the block where it added liquidity to `pair`.
+    /// Every part is best effort: old nonces need an archive node and the funding
+    /// source needs the explorer API (`ETHERSCAN_API_KEY`).
+    pub async fn new(http: Arc<Provider<Http>>, dex: &Dex, wallet: Address, pair: Address, block: U64) -> Self {
+        let first_block = first_nonce_block(&http, wallet, block).await;
+        let age_days = match first_block {
+            Some(first_block) => age_days(&http, first_block, block).await,
+            None => None,
+        };
+        let balance = http
+            .get_balance(wallet, Some(block.into()))
+            .await
+            .ok()
+            .map(|balance| u256_amount(balance, 18));
+        let (launches, rugs) = match &dex.store {
+            Some(store) => match store.deployer_history(&dex.chain, wallet, pair) {
+                Ok((launches, rugs)) => (Some(launches), Some(rugs)),
+                Err(_) => (None, None),
+            },
+            None => (None, None),
+        };
+
+        Self {
+            address: wallet,
+            first_block,
+            age_days,
+            balance,
+            funder: funder(&dex.chain, wallet).await,
+            launches,
+            rugs,
+        }
+    }
+}
+
+impl fmt::Display for WalletProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.address)?;
+        match self.age_days {
+            Some(age) => write!(f, " | Age: {:.1} days", age)?,
+            None => write!(f, " | Age: unknown")?,
+        }
+        if let Some(balance) = self.balance {
+            write!(f, " | Balance: {:.4}", balance)?;
+        }
+        if let Some(funder) = self.funder {
+            write!(f, " | Funded by {:#x}", funder)?;
+        }
+        if let (Some(launches), Some(rugs)) = (self.launches, self.rugs) {
+            write!(f, " | Previous launches: {} ({} rugged)", launches, rugs)?;
+        }
+        Ok(())
+    }
+}
+
+/// First block where the nonce of the wallet is not zero, by binary search.
+async fn first_nonce_block(http: &Provider<Http>, wallet: Address, block: U64) -> Option<U64> {
+    let nonce = http.get_transaction_count(wallet, Some(block.into())).await.ok()?;
+    if nonce.is_zero() {
+        return None;
+    }
+    let (mut low, mut high) = (0, block.as_u64());
+    while low < high {
+        let middle = low + (high - low) / 2;
+        let nonce = http
+            .get_transaction_count(wallet, Some(U64::from(middle).into()))
+            .await
+            .ok()?;
+        if nonce.is_zero() {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Some(low.into())
+}
+
+/// Age of the wallet at `block`, so a backfilled launch shows how old the wallet was then.
+async fn age_days(http: &Provider<Http>, first_block: U64, block: U64) -> Option<f64> {
+    let first = http.get_block(first_block).await.ok()??.timestamp.as_u64();
+    let launch = http.get_block(block).await.ok()??.timestamp.as_u64();
+    Some(launch.saturating_sub(first) as f64 / 86_400.0)
+}
+
+/// Sender of the first transaction received by the wallet, from the explorer API.
+/// `None` on a chain without an explorer of its own, etherscan.io would answer for
+/// the Ethereum address.
+async fn funder(chain: &str, wallet: Address) -> Option<Address> {
+    let explorer = Token::own_explorer(chain)?;
+    let api_key = std::env::var("ETHERSCAN_API_KEY").ok()?;
+    let url = format!(
+        "https://api.{}api?module=account&action=txlist&address={:#x}&startblock=0&page=1&offset=20&sort=asc&apikey={}",
+        explorer,
+        wallet,
+        api_key
+    );
+    let response = reqwest::get(&url).await.ok()?.text().await.ok()?;
+    let response: Value = serde_json::from_str(&response).ok()?;
+    response["result"]
+        .as_array()?
+        .iter()
+        .filter(|transaction| {
+            transaction["to"].as_str().and_then(|to| to.parse::<Address>().ok()) == Some(wallet)
+        })
+        .find_map(|transaction| transaction["from"].as_str()?.parse().ok())
+}